@@ -1,60 +1,1298 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    ops::{BitOrAssign, RangeInclusive},
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use polyominos::{
+    board::{Board, SIZE},
     database::*,
+    enumerator::Enumerator,
     grid::{are_equal, transfer, Grid},
     grids::{block_grid::BlockGrid, naive::Naive},
+    identify,
+    lattice::{Hexagonal, Lattice, Square, Triangular},
     polyomino::*,
+    properties::Properties,
+    puzzle::Puzzle,
+    rank, redelmeier,
+    rotation::{Symmetry, SymmetryClass},
+    tiling, transfer_matrix,
 };
 
-const LIMIT: u8 = 12;
+/// Enumerate and count polyominoes
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
 
-fn main() {
-    let mut db = Database::<BlockGrid>::new();
-
-    loop {
-        let p = {
-            match db.pop() {
-                None => {
-                    db.flush();
-                    continue;
+#[derive(Subcommand)]
+enum Command {
+    /// Count polyominoes, or polyiamonds and polyhexes, by number of cells
+    Count(CountArgs),
+    /// List all polyominoes with the maximum number of squares
+    List {
+        #[command(flatten)]
+        enumeration: EnumerationArgs,
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+        /// Check each representative against the one picked with the naive grid implementation
+        #[arg(long)]
+        verify: bool,
+        /// Number of threads expanding each level. The polyominoes are then printed
+        /// once their level is complete instead of streamed
+        #[arg(short = 'j', long, default_value_t = 1, conflicts_with = "verify")]
+        threads: usize,
+    },
+    /// Print histograms of the geometric properties of the polyominoes,
+    /// by number of squares. The properties depending on the orientation
+    /// are the ones of the representatives
+    Properties {
+        #[command(flatten)]
+        enumeration: EnumerationArgs,
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+    },
+    /// Print the representative of a shape, given as rows of 'O' and '.'
+    /// from top to bottom, separated by '/' or new lines, and its name if it has one
+    Canon {
+        shape: String,
+        #[arg(long, default_value_t = Mode::OneSided)]
+        mode: Mode,
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+        /// Also print the index of the representative among the fixed polyominoes,
+        /// for at most 18 squares. This takes seconds past 14 squares and about
+        /// 20 seconds at 18
+        #[arg(long)]
+        index: bool,
+    },
+    /// Print the fixed polyomino of an index, as given by canon --index, for at most
+    /// 18 squares. This takes seconds past 14 squares and about 20 seconds at 18
+    Unrank {
+        squares: u8,
+        index: u128,
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+    },
+    /// Tile a rectangle with copies of a shape, given like for canon, turned and
+    /// possibly reflected, or find the smallest rectangle it tiles
+    Tile {
+        shape: String,
+        /// The rectangle to tile, WIDTHxHEIGHT, at most 32 in both directions. Without it,
+        /// the smallest rectangle by area is looked for, which gives the order of the shape
+        #[arg(long)]
+        rectangle: Option<Window>,
+        /// Also place the mirror images of the shape
+        #[arg(long)]
+        reflections: bool,
+        /// Largest area of the rectangles tried when looking for the smallest one
+        #[arg(long, default_value_t = 200)]
+        max_area: usize,
+    },
+    /// Place each piece once inside of a region, both given like for canon
+    Pack {
+        region: String,
+        /// A piece, which can be given several times
+        #[arg(long = "piece")]
+        pieces: Vec<String>,
+        /// Also place all polyominoes with this number of squares, at most 22, for the mode
+        #[arg(long, value_parser = clap::value_parser!(u8).range(1..=22))]
+        polyominoes: Option<u8>,
+        /// Fixed pieces are placed as given, one-sided ones may be turned,
+        /// and free ones also reflected
+        #[arg(long, default_value_t = Mode::Free)]
+        mode: Mode,
+        /// Only keep one of the solutions mapped to each other by the symmetries of the region
+        #[arg(long)]
+        distinct: bool,
+        /// Print the number of solutions instead of the first one
+        #[arg(long, conflicts_with = "list")]
+        count: bool,
+        /// Print all solutions instead of the first one
+        #[arg(long)]
+        list: bool,
+    },
+    /// Render the polyominoes of a level file
+    Render {
+        level: PathBuf,
+        /// Also render the positions where squares can be added
+        #[arg(long)]
+        mask: bool,
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+    },
+    /// Count fixed polyominoes with Redelmeier's algorithm, without storing them
+    Redelmeier {
+        /// Maximum number of squares, at most 22. The time grows about fourfold per
+        /// square, from a second at 16 squares to an hour at 22
+        #[arg(short = 'n', long, default_value_t = 12, value_parser = clap::value_parser!(u8).range(1..=22))]
+        max: u8,
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+    },
+    /// Count fixed polyominoes with Jensen's transfer-matrix algorithm
+    TransferMatrix {
+        /// Maximum number of squares
+        #[arg(short = 'n', long, default_value_t = 12, value_parser = clap::value_parser!(u8).range(1..=56))]
+        max: u8,
+        /// Only count the polyominoes whose bounding box has this number of rows, at most
+        /// 28 for the states of the transfer
+        #[arg(long, value_parser = clap::value_parser!(u8).range(1..=transfer_matrix::MAX_WIDTH as i64))]
+        width: Option<u8>,
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+    },
+    /// Enumerate with several processes, each owning the polyominoes of some
+    /// bounding-box dimensions. The shards exchange polyominoes through files
+    /// in a directory, which can be shared between machines
+    Shard {
+        #[command(flatten)]
+        shards: ShardArgs,
+        #[command(subcommand)]
+        command: ShardCommand,
+    },
+}
+
+#[derive(Args)]
+struct ShardArgs {
+    /// Number of shards
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..))]
+    shards: u32,
+    /// Directory of the files of the shards
+    #[arg(long, default_value = "shards")]
+    dir: PathBuf,
+    /// Grid implementation, which must be the same for all shards
+    #[arg(long, value_enum, default_value_t = Backend::Block)]
+    grid: Backend,
+}
+
+#[derive(Subcommand)]
+enum ShardCommand {
+    /// Split the first level, or a stored level, between the shards
+    Init {
+        #[arg(long, default_value_t = Mode::OneSided)]
+        mode: Mode,
+        /// Level file to split instead of the first level
+        #[arg(long)]
+        from_level: Option<PathBuf>,
+    },
+    /// Expand the level of a shard, and route the new polyominoes to their owners
+    Expand {
+        #[arg(long)]
+        shard: u32,
+        #[arg(long)]
+        level: u8,
+    },
+    /// Gather the polyominoes routed to a shard into its level
+    Merge {
+        #[arg(long)]
+        shard: u32,
+        #[arg(long)]
+        level: u8,
+    },
+    /// Print the counts of a level, summed over the shards
+    Total {
+        #[arg(long)]
+        level: u8,
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+    },
+    /// Run the expand and merge steps of all shards up to the given level,
+    /// starting from the last level known to all shards
+    Run {
+        /// Maximum number of squares, at most 22 like for count
+        #[arg(short = 'n', long, default_value_t = 12, value_parser = clap::value_parser!(u8).range(1..=22))]
+        max: u8,
+        #[arg(long, default_value_t = Mode::OneSided)]
+        mode: Mode,
+        /// Number of shard processes running at the same time, all by default
+        #[arg(long)]
+        jobs: Option<usize>,
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+    },
+}
+
+#[derive(Args)]
+struct EnumerationArgs {
+    /// Maximum number of squares, or cells on the other lattices. The database packs the
+    /// bounding boxes of the representatives, with their margin, in 192 bits
+    /// (`Packed::MAX_AREA`), which holds 22 squares, and down to 11 cells on the other
+    /// lattices depending on the mode, as given by `Lattice::max_cells`
+    #[arg(short = 'n', long, default_value_t = 12, value_parser = clap::value_parser!(u8).range(1..=22))]
+    max: u8,
+    /// Grid implementation used for the enumeration
+    #[arg(long, value_enum, default_value_t = Backend::Block)]
+    grid: Backend,
+    /// Which transformations identify two polyominoes
+    #[arg(long, default_value_t = Mode::OneSided)]
+    mode: Mode,
+    /// Only enumerate the polyominoes fitting a window of WIDTHxHEIGHT squares,
+    /// turned if the mode identifies rotations
+    #[arg(long)]
+    window: Option<Window>,
+}
+
+#[derive(Args)]
+struct CountArgs {
+    #[command(flatten)]
+    enumeration: EnumerationArgs,
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+    /// Print the number of polyominoes with the maximum number of squares
+    /// by dimension of their bounding box, instead of the counts by level
+    #[arg(long)]
+    by_size: bool,
+    /// Check each representative against the one picked with the naive grid implementation
+    #[arg(long)]
+    verify: bool,
+    /// Lattice of the cells: polyominoes on the square lattice, polyiamonds on the
+    /// triangular one and polyhexes on the hexagonal one. Symmetry classes are only
+    /// counted, and windows only supported, on the square lattice
+    #[arg(long, value_enum, default_value_t = LatticeKind::Square)]
+    lattice: LatticeKind,
+    /// Number of threads expanding each level. Checkpoints are only written
+    /// between two levels when it is above 1
+    #[arg(short = 'j', long, default_value_t = 1, conflicts_with = "verify")]
+    threads: usize,
+    /// Checkpoint file, written periodically and on SIGINT. The enumeration
+    /// resumes from it if it exists
+    #[arg(long)]
+    checkpoint: Option<PathBuf>,
+    /// Seconds between two checkpoints
+    #[arg(long, default_value_t = 60)]
+    checkpoint_period: u64,
+    /// Directory where each completed level is written
+    #[arg(long)]
+    save_levels: Option<PathBuf>,
+    /// Level file to start from
+    #[arg(long, conflicts_with = "checkpoint")]
+    from_level: Option<PathBuf>,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum Backend {
+    Naive,
+    Block,
+    Board,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum LatticeKind {
+    Square,
+    Triangular,
+    Hexagonal,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Text,
+    Csv,
+    Json,
+}
+
+/// What pack prints of the solutions
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Solutions {
+    First,
+    Count,
+    List,
+}
+
+/// Run a generic function with the grid implementation of the backend
+macro_rules! with_backend {
+    ($backend:expr, $f:ident($($arg:expr),*)) => {
+        match $backend {
+            Backend::Naive => $f::<Naive>($($arg),*),
+            Backend::Block => $f::<BlockGrid>($($arg),*),
+            Backend::Board => $f::<Board>($($arg),*),
+        }
+    };
+}
+
+/// Write the checkpoint next to its final place first, so that an interruption
+/// while writing cannot corrupt the previous checkpoint
+fn save_checkpoint<T, L>(db: &Database<T, L>, path: &Path) -> io::Result<()>
+where
+    T: Grid + BitOrAssign,
+    L: Lattice,
+{
+    let tmp = path.with_extension("tmp");
+    let mut w = BufWriter::new(File::create(&tmp)?);
+    db.save(&mut w)?;
+    w.flush()?;
+    drop(w);
+    fs::rename(tmp, path)
+}
+
+fn save_level<T, L>(db: &Database<T, L>, dir: &Path) -> io::Result<()>
+where
+    T: Grid + BitOrAssign,
+    L: Lattice,
+{
+    fs::create_dir_all(dir)?;
+    save_level_to(db, &dir.join(format!("level-{}.poly", db.counts().len())))
+}
+
+fn save_level_to<T, L>(db: &Database<T, L>, path: &Path) -> io::Result<()>
+where
+    T: Grid + BitOrAssign,
+    L: Lattice,
+{
+    let mut w = BufWriter::new(File::create(path)?);
+    db.save_level(&mut w)?;
+    w.flush()
+}
+
+fn load_level<T: Grid + BitOrAssign, L: Lattice>(path: &Path) -> Database<T, L> {
+    let mut r = BufReader::new(File::open(path).expect("Cannot open the level"));
+    Database::from_level(&mut r).expect("Cannot read the level")
+}
+
+/// Same as [`Database::expand`], but checking each representative against [`Naive`]
+fn expand_verified<T, L>(db: &mut Database<T, L>, p: &Polyomino<T>)
+where
+    T: Grid + BitOrAssign,
+    L: Lattice,
+{
+    for p in db.children(p).into_iter() {
+        let witness = Polyomino::<Naive> {
+            square_count: p.square_count,
+            dimension: p.dimension,
+            repr: transfer(&p.repr),
+            mask: transfer(&p.mask),
+        };
+
+        let (smallest, s) = L::canonical(p, db.mode());
+        let (witness_smallest, _) = L::canonical(witness.clone(), db.mode());
+        let witness_image = L::apply(&witness, s);
+
+        are_equal(&smallest.repr, &witness_image.repr);
+        are_equal(&smallest.repr, &witness_smallest.repr);
+        are_equal(&smallest.mask, &witness_image.mask);
+
+        db.register(smallest);
+    }
+}
+
+/// Process the database until all polyominoes with `args.enumeration.max` squares are known
+fn enumerate<T, L>(db: &mut Database<T, L>, args: &CountArgs)
+where
+    T: Grid + BitOrAssign,
+    L: Lattice,
+{
+    let interrupted = Arc::new(AtomicBool::new(false));
+    if args.checkpoint.is_some() {
+        let interrupted = interrupted.clone();
+        ctrlc::set_handler(move || interrupted.store(true, Ordering::Relaxed))
+            .expect("Cannot set the SIGINT handler");
+    }
+    let checkpoint_period = Duration::from_secs(args.checkpoint_period);
+    let mut last_checkpoint = Instant::now();
+
+    while db.counts().len() < args.enumeration.max as usize {
+        // The database is in a consistent state between two polyominoes
+        if let Some(checkpoint) = &args.checkpoint {
+            if interrupted.load(Ordering::Relaxed) {
+                save_checkpoint(db, checkpoint).expect("Cannot write the checkpoint");
+                eprintln!("Interrupted, state saved in {}", checkpoint.display());
+                std::process::exit(130);
+            }
+            if last_checkpoint.elapsed() >= checkpoint_period {
+                save_checkpoint(db, checkpoint).expect("Cannot write the checkpoint");
+                last_checkpoint = Instant::now();
+            }
+        }
+
+        if args.threads > 1 {
+            db.expand_level(args.threads);
+            if let Some(dir) = &args.save_levels {
+                save_level(db, dir).expect("Cannot write the level");
+            }
+            continue;
+        }
+
+        match db.pop() {
+            None => {
+                db.flush();
+                if let Some(dir) = &args.save_levels {
+                    save_level(db, dir).expect("Cannot write the level");
                 }
-                Some(p) => p,
             }
-        };
+            Some(p) if args.verify => expand_verified(db, &p),
+            Some(p) => db.expand(&p),
+        }
+    }
 
-        if p.square_count >= LIMIT {
-            break;
+    if let Some(checkpoint) = &args.checkpoint {
+        if checkpoint.exists() {
+            fs::remove_file(checkpoint).expect("Cannot remove the checkpoint");
         }
+    }
+}
 
-        let declinaison = decline(&p);
+fn count<T: Grid + BitOrAssign>(args: &CountArgs) {
+    let mode = args.enumeration.mode;
+    if args.lattice != LatticeKind::Square && args.enumeration.window.is_some() {
+        eprintln!("Windows are only supported on the square lattice");
+        process::exit(1);
+    }
+    if args.lattice != LatticeKind::Square && args.by_size {
+        // The sizes would be the ones of the sheared bounding boxes
+        eprintln!("Counts by size are only supported on the square lattice");
+        process::exit(1);
+    }
+    let (name, max_cells) = match args.lattice {
+        LatticeKind::Square => (Square::NAME, Square::max_cells(mode)),
+        LatticeKind::Triangular => (Triangular::NAME, Triangular::max_cells(mode)),
+        LatticeKind::Hexagonal => (Hexagonal::NAME, Hexagonal::max_cells(mode)),
+    };
+    if args.enumeration.max as usize > max_cells {
+        eprintln!(
+            "At most {max_cells} cells fit the database on the {name} lattice in the {mode} mode"
+        );
+        process::exit(1);
+    }
+    match args.lattice {
+        LatticeKind::Square => count_on(args, new_database::<T>(&args.enumeration)),
+        LatticeKind::Triangular => count_on(args, Database::<T, Triangular>::with_mode(mode)),
+        LatticeKind::Hexagonal => count_on(args, Database::<T, Hexagonal>::with_mode(mode)),
+    }
+}
 
-        for p in declinaison.into_iter() {
-            println!("Searching smallest rotation of\n{:?}", p);
-            // println!("p repr:\n{:?}", p.repr);
-            // println!("p mask:\n{:?}", p.mask);
+/// Count the polyforms of the lattice, starting from the fresh database unless
+/// the enumeration resumes from a checkpoint or a level
+fn count_on<T, L>(args: &CountArgs, fresh: Database<T, L>)
+where
+    T: Grid + BitOrAssign,
+    L: Lattice,
+{
+    let mut db = match (&args.checkpoint, &args.from_level) {
+        (Some(checkpoint), _) if checkpoint.exists() => {
+            let mut r = BufReader::new(File::open(checkpoint).expect("Cannot open the checkpoint"));
+            let db = Database::<T, L>::load(&mut r).expect("Cannot read the checkpoint");
+            eprintln!("Resuming from {}", checkpoint.display());
+            db
+        }
+        (_, Some(level)) => load_level(level),
+        _ => fresh,
+    };
+    if db.mode() != args.enumeration.mode {
+        eprintln!("Warning: the stored state uses the {} mode", db.mode());
+    }
+    if db.window() != args.enumeration.window {
+        match db.window() {
+            Some(window) => eprintln!("Warning: the stored state uses the {window} window"),
+            None => eprintln!("Warning: the stored state uses no window"),
+        }
+    }
 
-            let witness = Polyomino::<Naive> {
-                square_count: p.square_count,
-                dimension: p.dimension,
-                repr: transfer(&p.repr),
-                mask: transfer(&p.mask),
-            };
+    enumerate(&mut db, args);
+
+    if args.by_size {
+        let sizes = db.counts_by_size().expect("The last level is not complete");
+        print_sizes(&sizes, args.format);
+        return;
+    }
+    let mut totals = Totals::default();
+    totals.add(&db);
+    print_counts(&totals, args.format);
+}
+
+fn new_database<T: Grid + BitOrAssign>(args: &EnumerationArgs) -> Database<T> {
+    match args.window {
+        Some(window) => Database::bounded(args.mode, window),
+        None => Database::with_mode(args.mode),
+    }
+}
+
+fn print_sizes(sizes: &BTreeMap<(usize, usize), u128>, format: Format) {
+    match format {
+        Format::Text => {
+            for ((width, height), cnt) in sizes.iter() {
+                println!("{width}x{height}: {cnt}");
+            }
+        }
+        Format::Csv => {
+            println!("width,height,count");
+            for ((width, height), cnt) in sizes.iter() {
+                println!("{width},{height},{cnt}");
+            }
+        }
+        Format::Json => {
+            let rows = sizes
+                .iter()
+                .map(|((width, height), cnt)| {
+                    format!("{{\"width\":{width},\"height\":{height},\"count\":{cnt}}}")
+                })
+                .collect::<Vec<_>>();
+            println!("[{}]", rows.join(","));
+        }
+    }
+}
+
+/// Counts, stats and symmetry classes by level, summed over databases
+#[derive(Default)]
+struct Totals {
+    counts: Vec<u128>,
+    stats: Vec<u128>,
+    classes: Vec<[u128; SymmetryClass::ALL.len()]>,
+    hole_free: Vec<u128>,
+}
+
+impl Totals {
+    fn add<T: Grid + BitOrAssign, L: Lattice>(&mut self, db: &Database<T, L>) {
+        fn add_to<V: Default + Clone>(totals: &mut Vec<V>, values: &[V], f: impl Fn(&mut V, &V)) {
+            if totals.len() < values.len() {
+                totals.resize(values.len(), V::default());
+            }
+            totals
+                .iter_mut()
+                .zip(values.iter())
+                .for_each(|(t, v)| f(t, v));
+        }
+
+        add_to(&mut self.counts, db.counts().as_slice(), |t, v| *t += v);
+        add_to(&mut self.stats, db.stats().as_slice(), |t, v| *t += v);
+        add_to(&mut self.classes, db.classes().as_slice(), |t, v| {
+            t.iter_mut().zip(v.iter()).for_each(|(t, v)| *t += v)
+        });
+        add_to(&mut self.hole_free, db.hole_free().as_slice(), |t, v| {
+            *t += v
+        });
+    }
+}
+
+fn print_counts(totals: &Totals, format: Format) {
+    let rows = totals
+        .counts
+        .iter()
+        .zip(totals.stats.iter())
+        .zip(totals.classes.iter())
+        .zip(totals.hole_free.iter())
+        .enumerate();
+    match format {
+        Format::Text => {
+            for (i, (((cnt, stat), classes), hole_free)) in rows {
+                let squares = i + 1;
+                let redundant = stat - cnt;
+                println!("With {squares} squares: {cnt} ({redundant} redundancies)");
+                let classes = SymmetryClass::ALL
+                    .iter()
+                    .zip(classes.iter())
+                    .filter(|(_, &count)| count != 0)
+                    .map(|(class, count)| format!("{}: {count}", class.name()))
+                    .collect::<Vec<_>>();
+                // Only the square lattice has symmetry classes
+                if !classes.is_empty() {
+                    println!("    by symmetry: {}", classes.join(", "));
+                }
+                println!("    without holes: {hole_free}");
+            }
+        }
+        Format::Csv => {
+            let names = SymmetryClass::ALL.map(|class| class.name());
+            println!("squares,count,redundancies,{},hole_free", names.join(","));
+            for (i, (((cnt, stat), classes), hole_free)) in rows {
+                let classes = classes.map(|count| count.to_string());
+                println!(
+                    "{},{cnt},{},{},{hole_free}",
+                    i + 1,
+                    stat - cnt,
+                    classes.join(",")
+                );
+            }
+        }
+        Format::Json => {
+            let rows = rows
+                .map(|(i, (((cnt, stat), classes), hole_free))| {
+                    let classes = SymmetryClass::ALL
+                        .iter()
+                        .zip(classes.iter())
+                        .map(|(class, count)| format!("\"{}\":{count}", class.name()))
+                        .collect::<Vec<_>>();
+                    format!(
+                        "{{\"squares\":{},\"count\":{cnt},\"redundancies\":{},\"classes\":{{{}}},\"hole_free\":{hole_free}}}",
+                        i + 1,
+                        stat - cnt,
+                        classes.join(",")
+                    )
+                })
+                .collect::<Vec<_>>();
+            println!("[{}]", rows.join(","));
+        }
+    }
+}
 
-            let (smallest, r) = smallest_rotation(p);
+fn shard_level_path(dir: &Path, level: u8, shard: u32) -> PathBuf {
+    dir.join(format!("level-{level}.shard-{shard}.poly"))
+}
 
-            are_equal(&smallest.repr, &witness.repr.rotate(witness.dimension, r));
-            are_equal(&smallest.mask, &witness.mask.rotate(witness.dimension, r));
+fn routed_path(dir: &Path, level: u8, from: u32, to: u32) -> PathBuf {
+    dir.join(format!("routed-{level}.{from}-{to}.poly"))
+}
+
+fn shard_state_path(dir: &Path, shard: u32) -> PathBuf {
+    dir.join(format!("state-{shard}.poly"))
+}
+
+fn shard_init<T: Grid + BitOrAssign>(args: &ShardArgs, mode: Mode, from_level: Option<&Path>) {
+    let db = match from_level {
+        Some(level) => load_level::<T, Square>(level),
+        None => Database::<T>::with_mode(mode),
+    };
+    let level = db.counts().len() as u8;
+
+    fs::create_dir_all(&args.dir).expect("Cannot create the directory of the shards");
+    let shards = db
+        .split(args.shards as usize)
+        .expect("Cannot split the level");
+    for (k, shard) in shards.iter().enumerate() {
+        save_level_to(shard, &shard_level_path(&args.dir, level, k as u32))
+            .expect("Cannot write the level");
+    }
+}
 
-            println!("Smallest found:");
-            println!("{smallest:?}");
+/// Expand the level of the shard, writing the new polyominoes to one file for each
+/// shard, and the state of the shard to resume with [`shard_merge`]
+fn shard_expand<T: Grid + BitOrAssign>(args: &ShardArgs, shard: u32, level: u8) {
+    let mut db = load_level::<T, Square>(&shard_level_path(&args.dir, level, shard));
+    let mut outboxes = (0..args.shards)
+        .map(|to| File::create(routed_path(&args.dir, level + 1, shard, to)).map(BufWriter::new))
+        .collect::<io::Result<Vec<_>>>()
+        .expect("Cannot create the routed files");
 
-            db.register(smallest);
+    db.route(&mut outboxes)
+        .expect("Cannot write the routed polyominoes");
+    for w in outboxes.iter_mut() {
+        w.flush().expect("Cannot write the routed polyominoes");
+    }
+    save_checkpoint(&db, &shard_state_path(&args.dir, shard)).expect("Cannot write the state");
+}
+
+/// Register the polyominoes routed to the shard and write its new level
+fn shard_merge<T: Grid + BitOrAssign>(args: &ShardArgs, shard: u32, level: u8) {
+    let state = shard_state_path(&args.dir, shard);
+    let mut r = BufReader::new(File::open(&state).expect("Cannot open the state"));
+    let mut db = Database::<T>::load(&mut r).expect("Cannot read the state");
+
+    for from in 0..args.shards {
+        let path = routed_path(&args.dir, level, from, shard);
+        let mut r = BufReader::new(File::open(path).expect("Cannot open the routed polyominoes"));
+        db.receive(&mut r)
+            .expect("Cannot read the routed polyominoes");
+    }
+    db.flush();
+    save_level_to(&db, &shard_level_path(&args.dir, level, shard)).expect("Cannot write the level");
+
+    // The routed files are only removed once the level is safely written
+    for from in 0..args.shards {
+        fs::remove_file(routed_path(&args.dir, level, from, shard))
+            .expect("Cannot remove the routed polyominoes");
+    }
+    fs::remove_file(state).expect("Cannot remove the state");
+}
+
+fn shard_total<T: Grid + BitOrAssign>(args: &ShardArgs, level: u8, format: Format) {
+    let mut totals = Totals::default();
+    for shard in 0..args.shards {
+        totals.add(&load_level::<T, Square>(&shard_level_path(
+            &args.dir, level, shard,
+        )));
+    }
+    print_counts(&totals, format);
+}
+
+/// Run a step for all shards, each in its own process, with at most `jobs` at the same time
+fn run_shard_step(args: &ShardArgs, step: &str, level: u8, jobs: usize) {
+    let exe = std::env::current_exe().expect("Cannot find the executable");
+    let grid = args.grid.to_possible_value().unwrap();
+    let shards: Vec<_> = (0..args.shards).collect();
+    for shards in shards.chunks(jobs.max(1)) {
+        let children = shards
+            .iter()
+            .map(|shard| {
+                process::Command::new(&exe)
+                    .arg("shard")
+                    .args(["--shards", &args.shards.to_string()])
+                    .arg("--dir")
+                    .arg(&args.dir)
+                    .args(["--grid", grid.get_name()])
+                    .arg(step)
+                    .args(["--shard", &shard.to_string()])
+                    .args(["--level", &level.to_string()])
+                    .spawn()
+                    .expect("Cannot start a shard")
+            })
+            .collect::<Vec<_>>();
+        for mut child in children.into_iter() {
+            let status = child.wait().expect("Cannot wait for a shard");
+            if !status.success() {
+                eprintln!("A shard failed to {step} level {level}");
+                process::exit(1);
+            }
         }
     }
+}
 
-    for (i, (cnt, stat)) in db.counts().zip(db.stats()).enumerate() {
-        let squares = i + 1;
-        let redundant = stat - cnt;
-        println!("With {squares} squares: {cnt} ({redundant} redundancies)")
+fn shard_run(args: &ShardArgs, max: u8, mode: Mode, jobs: Option<usize>, format: Format) {
+    let complete = |level| (0..args.shards).all(|k| shard_level_path(&args.dir, level, k).exists());
+    let start = match (1..=max).rev().find(|&level| complete(level)) {
+        Some(level) => {
+            let path = shard_level_path(&args.dir, level, 0);
+            let mut r = BufReader::new(File::open(path).expect("Cannot open the level"));
+            let stored = Database::<Naive>::level_mode(&mut r).expect("Cannot read the level");
+            if stored != mode {
+                eprintln!(
+                    "The shards in {} use the {stored} mode, not the {mode} mode",
+                    args.dir.display()
+                );
+                process::exit(1);
+            }
+            level
+        }
+        None => {
+            with_backend!(args.grid, shard_init(args, mode, None));
+            1
+        }
+    };
+
+    let jobs = jobs.unwrap_or(args.shards as usize);
+    for level in start..max {
+        run_shard_step(args, "expand", level, jobs);
+        run_shard_step(args, "merge", level + 1, jobs);
+    }
+    with_backend!(args.grid, shard_total(args, max, format));
+}
+
+fn print_fixed_counts(counts: &[u128], format: Format) {
+    match format {
+        Format::Text => {
+            for (i, cnt) in counts.iter().enumerate() {
+                println!("With {} squares: {cnt}", i + 1);
+            }
+        }
+        Format::Csv => {
+            println!("squares,count");
+            for (i, cnt) in counts.iter().enumerate() {
+                println!("{},{cnt}", i + 1);
+            }
+        }
+        Format::Json => {
+            let rows = counts
+                .iter()
+                .enumerate()
+                .map(|(i, cnt)| format!("{{\"squares\":{},\"count\":{cnt}}}", i + 1))
+                .collect::<Vec<_>>();
+            println!("[{}]", rows.join(","));
+        }
+    }
+}
+
+fn enumerator<'a, T: Grid + BitOrAssign>(
+    args: &EnumerationArgs,
+    sizes: RangeInclusive<usize>,
+) -> Enumerator<'a, T> {
+    let enumerator = Enumerator::<T>::range(sizes).mode(args.mode);
+    match args.window {
+        Some(window) => enumerator.window(window),
+        None => enumerator,
+    }
+}
+
+/// Stream the polyominoes as they are enumerated, without storing their level,
+/// unless they are verified or expanded by several threads
+fn list<T: Grid + BitOrAssign>(
+    args: &EnumerationArgs,
+    format: Format,
+    verify: bool,
+    threads: usize,
+) {
+    let max = args.max as usize;
+    if !verify && threads == 1 {
+        print_polyominoes(enumerator::<T>(args, max..=max), format, false);
+        return;
+    }
+
+    let mut db = new_database::<T>(args);
+    while db.counts().len() < max {
+        if threads > 1 {
+            db.expand_level(threads);
+            continue;
+        }
+        match db.pop() {
+            None => db.flush(),
+            Some(p) => expand_verified(&mut db, &p),
+        }
+    }
+    print_polyominoes(db.to_queue().into_iter(), format, false);
+}
+
+/// Names of the numeric properties, and of the ones which are true or false
+const VALUES: [&str; 9] = [
+    "perimeter",
+    "site_perimeter",
+    "width",
+    "height",
+    "left",
+    "right",
+    "bottom",
+    "top",
+    "domino_tilings",
+];
+const FLAGS: [&str; 5] = [
+    "row_convex",
+    "column_convex",
+    "convex",
+    "directed",
+    "tree_like",
+];
+
+/// The histogram of each numeric property, and the number of polyominoes with each flag,
+/// for a number of squares
+#[derive(Default)]
+struct Histograms {
+    values: [BTreeMap<u128, u128>; VALUES.len()],
+    flags: [u128; FLAGS.len()],
+}
+
+impl Histograms {
+    fn add(&mut self, properties: &Properties) {
+        let sides = properties.sides;
+        let values = [
+            properties.perimeter as u128,
+            properties.site_perimeter as u128,
+            properties.width as u128,
+            properties.height as u128,
+            sides.left as u128,
+            sides.right as u128,
+            sides.bottom as u128,
+            sides.top as u128,
+            properties.domino_tilings,
+        ];
+        for (histogram, value) in self.values.iter_mut().zip(values) {
+            *histogram.entry(value).or_insert(0) += 1;
+        }
+
+        let flags = [
+            properties.row_convex,
+            properties.column_convex,
+            properties.convex(),
+            properties.directed,
+            properties.tree_like,
+        ];
+        for (count, flag) in self.flags.iter_mut().zip(flags) {
+            *count += flag as u128;
+        }
+    }
+}
+
+fn properties<T: Grid + BitOrAssign>(args: &EnumerationArgs, format: Format) {
+    let max = args.max as usize;
+    let mut levels: Vec<Histograms> = (0..max).map(|_| Histograms::default()).collect();
+    for p in enumerator::<T>(args, 1..=max) {
+        levels[p.square_count as usize - 1].add(&Properties::of(&p));
+    }
+
+    match format {
+        Format::Text => {
+            for (i, level) in levels.iter().enumerate() {
+                println!("With {} squares:", i + 1);
+                for (name, histogram) in VALUES.iter().zip(level.values.iter()) {
+                    let histogram = histogram
+                        .iter()
+                        .map(|(value, count)| format!("{value}: {count}"))
+                        .collect::<Vec<_>>();
+                    println!("    {name}: {}", histogram.join(", "));
+                }
+                let flags = FLAGS
+                    .iter()
+                    .zip(level.flags.iter())
+                    .map(|(name, count)| format!("{name}: {count}"))
+                    .collect::<Vec<_>>();
+                println!("    {}", flags.join(", "));
+            }
+        }
+        Format::Csv => {
+            println!("squares,property,value,count");
+            for (i, level) in levels.iter().enumerate() {
+                for (name, histogram) in VALUES.iter().zip(level.values.iter()) {
+                    for (value, count) in histogram.iter() {
+                        println!("{},{name},{value},{count}", i + 1);
+                    }
+                }
+                for (name, count) in FLAGS.iter().zip(level.flags.iter()) {
+                    println!("{},{name},true,{count}", i + 1);
+                }
+            }
+        }
+        Format::Json => {
+            let rows = levels
+                .iter()
+                .enumerate()
+                .map(|(i, level)| {
+                    let mut fields = vec![format!("\"squares\":{}", i + 1)];
+                    for (name, histogram) in VALUES.iter().zip(level.values.iter()) {
+                        let histogram = histogram
+                            .iter()
+                            .map(|(value, count)| format!("\"{value}\":{count}"))
+                            .collect::<Vec<_>>();
+                        fields.push(format!("\"{name}\":{{{}}}", histogram.join(",")));
+                    }
+                    for (name, count) in FLAGS.iter().zip(level.flags.iter()) {
+                        fields.push(format!("\"{name}\":{count}"));
+                    }
+                    format!("{{{}}}", fields.join(","))
+                })
+                .collect::<Vec<_>>();
+            println!("[{}]", rows.join(","));
+        }
+    }
+}
+
+fn render<T: Grid + BitOrAssign>(level: &Path, mask: bool, format: Format) {
+    let db = load_level::<T, Square>(level);
+    print_polyominoes(db.to_queue().into_iter(), format, mask);
+}
+
+/// Exit with the error, if any
+fn or_exit<T>(result: Result<T, String>) -> T {
+    result.unwrap_or_else(|error| {
+        eprintln!("{error}");
+        process::exit(1);
+    })
+}
+
+fn parse_polyomino(shape: &str) -> Polyomino<Naive> {
+    or_exit(identify::identify_shape(shape, Mode::Fixed)).canonical
+}
+
+/// Exit unless polyominoes with this number of squares can be ranked
+fn check_rankable(squares: usize) {
+    if squares == 0 {
+        eprintln!("There are no polyominoes with zero square");
+        process::exit(1);
+    }
+    if squares > rank::MAX_SQUARES {
+        eprintln!(
+            "Only polyominoes with at most {} squares can be ranked",
+            rank::MAX_SQUARES
+        );
+        process::exit(1);
+    }
+}
+
+fn canon(shape: &str, mode: Mode, format: Format, index: bool) {
+    let id = or_exit(identify::identify_shape::<Naive>(shape, mode));
+    let symmetry = format_symmetry(id.symmetry);
+    let index = index.then(|| {
+        check_rankable(id.square_count);
+        rank::rank(&id.canonical, mode)
+    });
+    match format {
+        Format::Text => {
+            if let Some(name) = id.name {
+                println!("Name: {name}");
+            }
+            if let Some(index) = index {
+                println!("Index: {index}");
+            }
+            println!("Symmetry: {symmetry}");
+            print_polyominoes([id.canonical].into_iter(), format, false);
+        }
+        Format::Csv => {
+            if let Some(name) = id.name {
+                println!("name,{name}");
+            }
+            if let Some(index) = index {
+                println!("index,{index}");
+            }
+            println!("symmetry,{symmetry}");
+            print_polyominoes([id.canonical].into_iter(), format, false);
+        }
+        Format::Json => {
+            let name = id
+                .name
+                .map_or("null".to_string(), |name| format!("\"{name}\""));
+            let index = index.map_or("null".to_string(), |index| index.to_string());
+            println!(
+                "{{\"name\":{name},\"index\":{index},\"symmetry\":\"{symmetry}\",\"polyomino\":{}}}",
+                polyomino_to_json(&id.canonical)
+            )
+        }
+    }
+}
+
+fn unrank(squares: u8, index: u128, format: Format) {
+    check_rankable(squares as usize);
+    let mut ranking = rank::Ranking::new(squares as usize);
+    if index >= ranking.count() {
+        eprintln!(
+            "There are {} fixed polyominoes with {squares} squares",
+            ranking.count()
+        );
+        process::exit(1);
+    }
+    let p = ranking.unrank::<Naive>(index);
+    print_polyominoes([p].into_iter(), format, false);
+}
+
+fn tile(shape: &str, rectangle: Option<Window>, reflections: bool, max_area: usize) {
+    let p = parse_polyomino(shape);
+    let (width, height) = match rectangle {
+        Some(rectangle) if rectangle.width as usize > SIZE || rectangle.height as usize > SIZE => {
+            eprintln!("The rectangle does not fit a {SIZE}x{SIZE} board");
+            std::process::exit(1);
+        }
+        Some(rectangle) => (rectangle.width as usize, rectangle.height as usize),
+        None => match tiling::order(&p, reflections, max_area) {
+            Some(dimension) => dimension,
+            None => {
+                eprintln!("The shape tiles no rectangle of at most {max_area} squares");
+                std::process::exit(1);
+            }
+        },
+    };
+
+    match tiling::tile_rectangle(&p, width, height, reflections) {
+        Some(tiling) => {
+            let copies = width * height / p.square_count as usize;
+            println!("{width}x{height} rectangle, with {copies} copies:");
+            println!("{tiling}");
+        }
+        None => {
+            eprintln!("The shape does not tile the {width}x{height} rectangle");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn pack(
+    region: &str,
+    shapes: &[String],
+    polyominoes: Option<u8>,
+    mode: Mode,
+    distinct: bool,
+    output: Solutions,
+) {
+    let cells = or_exit(identify::parse_shape(region));
+    if cells.iter().any(|&(x, y)| x >= SIZE || y >= SIZE) {
+        eprintln!("The region does not fit a {SIZE}x{SIZE} board");
+        std::process::exit(1);
+    }
+    let mut grid = Naive::new();
+    for (x, y) in cells {
+        grid.set(x, y);
+    }
+    let mut pieces: Vec<_> = shapes.iter().map(|shape| parse_polyomino(shape)).collect();
+    if let Some(squares) = polyominoes {
+        pieces.extend(Enumerator::<Naive>::new(squares as usize).mode(mode));
+    }
+    let puzzle = Puzzle::new(&pieces, &grid, mode);
+
+    match (output, distinct) {
+        (Solutions::Count, true) => println!("{}", puzzle.distinct_solutions().len()),
+        (Solutions::Count, false) => println!("{}", puzzle.count()),
+        (Solutions::List, true) => {
+            for solution in puzzle.distinct_solutions() {
+                println!("{solution}\n");
+            }
+        }
+        (Solutions::List, false) => {
+            puzzle.solve(|solution| {
+                println!("{solution}\n");
+                true
+            });
+        }
+        (Solutions::First, _) => match puzzle.first() {
+            Some(solution) => println!("{solution}"),
+            None => {
+                eprintln!("The puzzle has no solution");
+                std::process::exit(1);
+            }
+        },
+    }
+}
+
+fn format_symmetry(s: Symmetry) -> String {
+    let rotation = format!("{:?}", s.rotation);
+    if s.mirror {
+        format!("mirror {rotation}")
+    } else {
+        rotation
+    }
+}
+
+fn polyomino_to_json<T: Grid>(p: &Polyomino<T>) -> String {
+    let (width, height) = p.size();
+    let cells = p
+        .cells()
+        .iter()
+        .map(|(x, y)| format!("[{x},{y}]"))
+        .collect::<Vec<_>>();
+    format!(
+        "{{\"squares\":{},\"width\":{width},\"height\":{height},\"cells\":[{}]}}",
+        p.square_count,
+        cells.join(",")
+    )
+}
+
+/// Render the polyomino as rows of 'O' and '.', from top to bottom.
+/// With the mask, the margin is also rendered, with '+' where squares can be added
+fn polyomino_to_text<T: Grid>(p: &Polyomino<T>, mask: bool) -> String {
+    let (xs, ys) = if mask {
+        (0..p.dimension.0 as usize, 0..p.dimension.1 as usize)
+    } else {
+        (1..p.dimension.0 as usize - 1, 1..p.dimension.1 as usize - 1)
+    };
+    let mut rows = vec![];
+    for y in ys.rev() {
+        let row = xs
+            .clone()
+            .map(|x| match (p.repr.get(x, y), mask && p.mask.get(x, y)) {
+                (true, _) => 'O',
+                (false, true) => '+',
+                (false, false) => '.',
+            })
+            .collect::<String>();
+        rows.push(row);
+    }
+    rows.join("\n")
+}
+
+fn print_polyominoes<T, I>(polyominoes: I, format: Format, mask: bool)
+where
+    T: Grid,
+    I: Iterator<Item = Polyomino<T>>,
+{
+    match format {
+        Format::Text => {
+            for p in polyominoes {
+                println!("{}\n", polyomino_to_text(&p, mask));
+            }
+        }
+        Format::Csv => {
+            println!("squares,width,height,cells");
+            for p in polyominoes {
+                let (width, height) = p.size();
+                let cells = p
+                    .cells()
+                    .iter()
+                    .map(|(x, y)| format!("{x}:{y}"))
+                    .collect::<Vec<_>>();
+                println!("{},{width},{height},{}", p.square_count, cells.join(" "));
+            }
+        }
+        Format::Json => {
+            let polyominoes = polyominoes
+                .map(|p| polyomino_to_json(&p))
+                .collect::<Vec<_>>();
+            println!("[{}]", polyominoes.join(","));
+        }
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    match &cli.command {
+        Command::Count(args) => with_backend!(args.enumeration.grid, count(args)),
+        Command::List {
+            enumeration,
+            format,
+            verify,
+            threads,
+        } => with_backend!(
+            enumeration.grid,
+            list(enumeration, *format, *verify, *threads)
+        ),
+        Command::Properties {
+            enumeration,
+            format,
+        } => with_backend!(enumeration.grid, properties(enumeration, *format)),
+        Command::Canon {
+            shape,
+            mode,
+            format,
+            index,
+        } => canon(shape, *mode, *format, *index),
+        Command::Unrank {
+            squares,
+            index,
+            format,
+        } => unrank(*squares, *index, *format),
+        Command::Pack {
+            region,
+            pieces,
+            polyominoes,
+            mode,
+            distinct,
+            count,
+            list,
+        } => {
+            let output = match (count, list) {
+                (true, _) => Solutions::Count,
+                (_, true) => Solutions::List,
+                _ => Solutions::First,
+            };
+            pack(region, pieces, *polyominoes, *mode, *distinct, output)
+        }
+        Command::Tile {
+            shape,
+            rectangle,
+            reflections,
+            max_area,
+        } => tile(shape, *rectangle, *reflections, *max_area),
+        Command::Render {
+            level,
+            mask,
+            format,
+        } => render::<BlockGrid>(level, *mask, *format),
+        Command::Redelmeier { max, format } => {
+            print_fixed_counts(&redelmeier::count_fixed(*max as usize), *format)
+        }
+        Command::TransferMatrix { max, width, format } => {
+            let counts = match width {
+                Some(width) => transfer_matrix::count_strip(*max as usize, *width as usize),
+                None => transfer_matrix::count_fixed(*max as usize),
+            };
+            print_fixed_counts(&counts, *format)
+        }
+        Command::Shard { shards, command } => match command {
+            ShardCommand::Init { mode, from_level } => {
+                with_backend!(
+                    shards.grid,
+                    shard_init(shards, *mode, from_level.as_deref())
+                )
+            }
+            ShardCommand::Expand { shard, level } => {
+                with_backend!(shards.grid, shard_expand(shards, *shard, *level))
+            }
+            ShardCommand::Merge { shard, level } => {
+                with_backend!(shards.grid, shard_merge(shards, *shard, *level))
+            }
+            ShardCommand::Total { level, format } => {
+                with_backend!(shards.grid, shard_total(shards, *level, *format))
+            }
+            ShardCommand::Run {
+                max,
+                mode,
+                jobs,
+                format,
+            } => shard_run(shards, *max, *mode, *jobs, *format),
+        },
     }
 }
 
@@ -64,4 +1302,4 @@ fn main() {
 // or in the y direction, or not increase (if the square is added in a crease
 // or a hole for example)
 // This can be used to split databases between sizes, to spread memory usage across
-// different nodes
+// different nodes, which is what the shard subcommand does
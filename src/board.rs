@@ -1,4 +1,7 @@
-use std::{fmt::Debug, ops::BitOrAssign};
+use std::{
+    fmt::{Debug, Write},
+    ops::BitOrAssign,
+};
 
 // #[repr(transparent)]
 // struct Block(u16);
@@ -31,7 +34,7 @@ use std::{fmt::Debug, ops::BitOrAssign};
 //     }
 // }
 
-use crate::rotation::Rotation;
+use crate::{grid::Grid, rotation::Rotation};
 
 pub const SIZE: usize = 32;
 
@@ -49,38 +52,86 @@ impl Board {
         Self { board: [0; SIZE] }
     }
 
-    pub fn set(&mut self, x: usize, y: usize) {
+    pub fn sub(&mut self, other: &Self) {
+        for x in 0..SIZE {
+            self.board[x] &= !other.board[x];
+        }
+    }
+
+    /// Whether all elements set to true in the other board are set in this one
+    pub fn contains(&self, other: &Self) -> bool {
+        self.board
+            .iter()
+            .zip(other.board.iter())
+            .all(|(a, b)| b & !a == 0)
+    }
+
+    /// Return the first element set to true, by column then by row
+    pub fn first(&self) -> Option<(usize, usize)> {
+        let x = self.board.iter().position(|&column| column != 0)?;
+        Some((x, self.board[x].trailing_zeros() as usize))
+    }
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Grid for Board {
+    const SIZE: usize = SIZE;
+
+    fn new() -> Self {
+        Board::new()
+    }
+
+    fn set(&mut self, x: usize, y: usize) {
         self.board[x] |= 1 << y;
     }
 
-    pub fn unset(&mut self, x: usize, y: usize) {
+    fn unset(&mut self, x: usize, y: usize) {
         self.board[x] &= u32::MAX - (1 << y);
     }
 
-    pub fn get(&self, x: usize, y: usize) -> bool {
+    fn get(&self, x: usize, y: usize) -> bool {
         if y >= SIZE {
             panic!("Whooat??! {x}, {y}")
         }
         ((self.board[x] >> y) & 1) != 0
     }
 
-    pub fn sub(&mut self, other: &Self) {
-        for x in 0..SIZE {
-            self.board[x] &= !other.board[x];
-        }
+    fn count(&self) -> u32 {
+        self.board.iter().map(|column| column.count_ones()).sum()
     }
 
-    pub fn count(&self) -> u32 {
-        self.board.iter().map(|column| column.count_ones()).sum()
+    fn reserve_space(&mut self, x: usize, y: usize) {
+        assert!(x < SIZE && y < SIZE);
+    }
+
+    fn get_bounding_box(&self) -> (usize, usize) {
+        let dim_x = match self.board.iter().rposition(|&column| column != 0) {
+            Some(x) => x + 1,
+            None => return (0, 0),
+        };
+        let dim_y = self.board[0..dim_x]
+            .iter()
+            .map(|column| SIZE - column.leading_zeros() as usize)
+            .max()
+            .unwrap_or(0);
+
+        (dim_x, dim_y)
     }
 
-    pub fn rotate(&self, dim: (u8, u8), r: &Rotation) -> Board {
+    fn rotate(&self, dim: (u8, u8), r: Rotation) -> Board {
         match r {
-            Rotation::R0 => self.clone(),
+            Rotation::R0 => *self,
             Rotation::R90 => {
+                // The whole board is rotated, so the bounding box ends up
+                // against the right side of the board
                 let mut new = Self::new();
                 rotate_90(&self.board, &mut new.board);
-                // new.shift_y((SIZE as isize - dim.1 as isize) as i8);
+                new.shift_x(dim.1 as isize - SIZE as isize);
                 new
             }
             Rotation::R180 => {
@@ -89,22 +140,52 @@ impl Board {
                 for x in 0..SIZE {
                     new.board[x] = self.board[SIZE - 1 - x].reverse_bits();
                 }
-                new.shift_x((SIZE as isize - dim.0 as isize) as i8);
-                new.shift_y((SIZE as isize - dim.1 as isize) as i8);
+                new.shift_x(dim.0 as isize - SIZE as isize);
+                new.shift_y(dim.1 as isize - SIZE as isize);
                 new
             }
             Rotation::R270 => {
-                // Same thing as a 90 rotation
+                // Same thing as a 90 rotation, the bounding box ends up
+                // against the top of the board
                 let mut new = Self::new();
                 rotate_270(&self.board, &mut new.board);
-                new.shift_x((SIZE as isize - dim.0 as isize) as i8);
+                new.shift_y(dim.0 as isize - SIZE as isize);
                 new
             }
         }
     }
 
-    pub fn shift_x(&mut self, amount: i8) {
-        if amount.abs() as isize > SIZE as isize {
+    fn cmp_rows(&self, other: &Self, dim: (u8, u8)) -> std::cmp::Ordering {
+        // The first difference is in the lowest row where two columns differ,
+        // in the leftmost of these columns
+        let rows = u32::MAX
+            .checked_shr(SIZE as u32 - dim.1 as u32)
+            .unwrap_or(0);
+        let first = (0..dim.0 as usize)
+            .map(|x| ((self.board[x] ^ other.board[x]) & rows, x))
+            .filter(|&(diff, _)| diff != 0)
+            .min_by_key(|&(diff, _)| diff.trailing_zeros());
+        match first {
+            None => std::cmp::Ordering::Equal,
+            Some((diff, x)) if self.board[x] & diff & diff.wrapping_neg() != 0 => {
+                std::cmp::Ordering::Greater
+            }
+            Some(_) => std::cmp::Ordering::Less,
+        }
+    }
+
+    fn mirror(&self, dim: (u8, u8)) -> Board {
+        // Simply reverse the array, then bring the bounding box back to the origin
+        let mut new = Self::new();
+        for x in 0..SIZE {
+            new.board[x] = self.board[SIZE - 1 - x];
+        }
+        new.shift_x(dim.0 as isize - SIZE as isize);
+        new
+    }
+
+    fn shift_x(&mut self, amount: isize) {
+        if amount.unsigned_abs() > SIZE {
             panic!("The shift amount is too big!");
         }
 
@@ -125,14 +206,36 @@ impl Board {
         }
     }
 
-    pub fn shift_y(&mut self, amount: i8) {
-        if amount.abs() as isize > SIZE as isize {
+    fn shift_y(&mut self, amount: isize) {
+        if amount.unsigned_abs() > SIZE {
             panic!("The shift amount is too big!");
         }
 
+        // Shifting a u32 by 32 overflows, the column is emptied instead
+        let shift = amount.unsigned_abs() as u32;
         for x in 0..SIZE {
-            self.board[x] <<= amount;
+            self.board[x] = if amount >= 0 {
+                self.board[x].checked_shl(shift).unwrap_or(0)
+            } else {
+                self.board[x].checked_shr(shift).unwrap_or(0)
+            };
+        }
+    }
+}
+
+impl Debug for Board {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let dim = self.get_bounding_box();
+        for y in (0..dim.1).rev() {
+            for x in 0..dim.0 {
+                f.write_char(if self.get(x, y) { 'O' } else { '.' })?;
+            }
+            if y != 0 {
+                f.write_char('\n')?;
+            }
         }
+
+        Ok(())
     }
 }
 
@@ -145,13 +248,17 @@ impl BitOrAssign for Board {
 }
 
 #[repr(transparent)]
+#[allow(dead_code)]
 struct M256([i32; 8]);
+#[cfg(target_arch = "x86_64")]
 use std::arch::x86_64::__m256i;
+#[cfg(target_arch = "x86_64")]
+#[allow(dead_code)]
 fn read(m: __m256i) -> M256 {
     use std::arch::x86_64::*;
     let mut res = [0i32; 8];
     unsafe {
-        let store_mask = _mm256_set1_epi32((0x80000000 as u32) as i32); // To avoid warning
+        let store_mask = _mm256_set1_epi32(0x80000000_u32 as i32); // To avoid warning
         _mm256_maskstore_epi32(res.as_mut_ptr(), store_mask, m);
     }
     M256(res)
@@ -167,6 +274,29 @@ impl Debug for M256 {
 }
 
 fn rotate_90(from: &InnerBoard, to: &mut InnerBoard) {
+    #[cfg(target_arch = "x86_64")]
+    if crate::cpu::has_avx2() {
+        // SAFETY: AVX2 support was checked at runtime
+        unsafe { rotate_90_avx2(from, to) };
+        return;
+    }
+
+    rotate_90_scalar(from, to)
+}
+
+fn rotate_90_scalar(from: &InnerBoard, to: &mut InnerBoard) {
+    // The new column x is made of the bits (SIZE - 1 - x) of the old columns
+    for (x, column) in to.iter_mut().enumerate() {
+        *column = 0;
+        for (y, old_column) in from.iter().enumerate() {
+            *column |= ((old_column >> (SIZE - 1 - x)) & 1) << y;
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn rotate_90_avx2(from: &InnerBoard, to: &mut InnerBoard) {
     // This gets funny
     // For a 90 trigonometric rotation
     // - all MSB of the unrotated columns must be in column 0
@@ -197,7 +327,7 @@ fn rotate_90(from: &InnerBoard, to: &mut InnerBoard) {
 
         for x in 0..4 {
             let col = _mm256_set_epi32(
-                from[x * 8 + 0] as i32,
+                from[x * 8] as i32,
                 from[x * 8 + 1] as i32,
                 from[x * 8 + 2] as i32,
                 from[x * 8 + 3] as i32,
@@ -233,7 +363,7 @@ fn rotate_90(from: &InnerBoard, to: &mut InnerBoard) {
             // println!("ordered: {:?}", read(ordered));
 
             let mut res = [0i32; 8];
-            let store_mask = _mm256_set1_epi32((0x80000000 as u32) as i32); // To avoid warning
+            let store_mask = _mm256_set1_epi32(0x80000000_u32 as i32); // To avoid warning
             _mm256_maskstore_epi32(res.as_mut_ptr(), store_mask, ordered);
             // TODO: stored as little indian
 
@@ -256,7 +386,7 @@ fn rotate_90(from: &InnerBoard, to: &mut InnerBoard) {
                 strip[0], strip[1], strip[2], strip[3], strip[4], strip[5], strip[6], strip[7],
             );
 
-            for (b, &pattern) in [0x80 as u8 as i8, 0x40, 0x20, 0x10, 0x08, 0x04, 0x02, 0x01]
+            for (b, &pattern) in [0x80_u8 as i8, 0x40, 0x20, 0x10, 0x08, 0x04, 0x02, 0x01]
                 .iter()
                 .enumerate()
             {
@@ -278,6 +408,29 @@ fn rotate_90(from: &InnerBoard, to: &mut InnerBoard) {
 }
 
 fn rotate_270(from: &InnerBoard, to: &mut InnerBoard) {
+    #[cfg(target_arch = "x86_64")]
+    if crate::cpu::has_avx2() {
+        // SAFETY: AVX2 support was checked at runtime
+        unsafe { rotate_270_avx2(from, to) };
+        return;
+    }
+
+    rotate_270_scalar(from, to)
+}
+
+fn rotate_270_scalar(from: &InnerBoard, to: &mut InnerBoard) {
+    // The new column x is made of the bits x of the old columns, in reverse order
+    for (x, column) in to.iter_mut().enumerate() {
+        *column = 0;
+        for (y, old_column) in from.iter().rev().enumerate() {
+            *column |= ((old_column >> x) & 1) << y;
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn rotate_270_avx2(from: &InnerBoard, to: &mut InnerBoard) {
     // Same logic as rotation_90.
     // The only change is that we apply a 180 rotation after.
     // This is equivalent of loading the new columns in reverse order and without flipping their bit order
@@ -306,7 +459,7 @@ fn rotate_270(from: &InnerBoard, to: &mut InnerBoard) {
 
         for x in 0..4 {
             let col = _mm256_set_epi32(
-                from[x * 8 + 0] as i32,
+                from[x * 8] as i32,
                 from[x * 8 + 1] as i32,
                 from[x * 8 + 2] as i32,
                 from[x * 8 + 3] as i32,
@@ -342,7 +495,7 @@ fn rotate_270(from: &InnerBoard, to: &mut InnerBoard) {
             // println!("ordered: {:?}", read(ordered));
 
             let mut res = [0i32; 8];
-            let store_mask = _mm256_set1_epi32((0x80000000 as u32) as i32); // To avoid warning
+            let store_mask = _mm256_set1_epi32(0x80000000_u32 as i32); // To avoid warning
             _mm256_maskstore_epi32(res.as_mut_ptr(), store_mask, ordered);
             // TODO: stored as little indian
 
@@ -365,7 +518,7 @@ fn rotate_270(from: &InnerBoard, to: &mut InnerBoard) {
                 strip[0], strip[1], strip[2], strip[3], strip[4], strip[5], strip[6], strip[7],
             );
 
-            for (b, &pattern) in [0x80 as u8 as i8, 0x40, 0x20, 0x10, 0x08, 0x04, 0x02, 0x01]
+            for (b, &pattern) in [0x80_u8 as i8, 0x40, 0x20, 0x10, 0x08, 0x04, 0x02, 0x01]
                 .iter()
                 .enumerate()
             {
@@ -385,3 +538,84 @@ fn rotate_270(from: &InnerBoard, to: &mut InnerBoard) {
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_board(seed: u64) -> InnerBoard {
+        let mut state = seed;
+        let mut board = [0; SIZE];
+        for column in board.iter_mut() {
+            // xorshift
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            *column = state as u32;
+        }
+        board
+    }
+
+    #[test]
+    fn test_board_rotate_scalar() {
+        let mut from = [0; SIZE];
+        from[2] = 1 << 5;
+        let mut to = [0; SIZE];
+        rotate_90_scalar(&from, &mut to);
+        assert_eq!(to[SIZE - 1 - 5], 1 << 2);
+        rotate_270_scalar(&from, &mut to);
+        assert_eq!(to[5], 1 << (SIZE - 1 - 2));
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_board_rotate_avx2_is_scalar() {
+        if !is_x86_feature_detected!("avx2") {
+            return;
+        }
+
+        for seed in 1..100 {
+            let from = random_board(seed);
+            let (mut scalar, mut avx2) = ([0; SIZE], [0; SIZE]);
+            rotate_90_scalar(&from, &mut scalar);
+            unsafe { rotate_90_avx2(&from, &mut avx2) };
+            assert_eq!(scalar, avx2);
+            rotate_270_scalar(&from, &mut scalar);
+            unsafe { rotate_270_avx2(&from, &mut avx2) };
+            assert_eq!(scalar, avx2);
+        }
+    }
+
+    #[test]
+    fn test_board_dim() {
+        assert_eq!(Board::new().get_bounding_box(), (0, 0));
+
+        let mut board = Board::new();
+        board.set(1, 2);
+        assert_eq!(board.get_bounding_box(), (2, 3));
+
+        board.set(2, 1);
+        assert_eq!(board.get_bounding_box(), (3, 3));
+
+        board.unset(1, 2);
+        assert_eq!(board.get_bounding_box(), (3, 2));
+
+        board.set(31, 31);
+        assert_eq!(board.get_bounding_box(), (32, 32));
+    }
+
+    #[test]
+    fn test_board_shift() {
+        let mut board = Board::new();
+        board.set(0, 0);
+        board.shift_y(31);
+        assert!(board.get(0, 31));
+        board.shift_x(31);
+        assert!(board.get(31, 31));
+        board.shift_y(-31);
+        board.shift_x(-31);
+        assert!(board.get(0, 0));
+        board.shift_y(32);
+        assert_eq!(board.count(), 0);
+    }
+}
@@ -0,0 +1,16 @@
+//! Runtime detection of the CPU features used by the vectorized code paths.
+//! Every vectorized function has a scalar counterpart giving identical results,
+//! which is used when the feature is missing.
+
+/// Return true if the AVX2 implementations can be used
+#[cfg(target_arch = "x86_64")]
+pub fn has_avx2() -> bool {
+    // The result is cached by the standard library
+    is_x86_feature_detected!("avx2")
+}
+
+/// Return true if the AVX2 implementations can be used
+#[cfg(not(target_arch = "x86_64"))]
+pub fn has_avx2() -> bool {
+    false
+}
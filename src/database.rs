@@ -1,25 +1,166 @@
 use std::{
-    collections::{BTreeMap, HashMap, VecDeque},
-    hash::Hash,
-    mem::replace,
+    collections::{hash_map::DefaultHasher, hash_map::Entry, BTreeMap, HashMap, VecDeque},
+    hash::{Hash, Hasher},
+    io::{self, Read, Write},
+    marker::PhantomData,
+    mem::take,
     ops::BitOrAssign,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
 };
 
-use crate::{grid::Grid, polyomino::Polyomino};
+use crate::{
+    encoding::*,
+    grid::Grid,
+    lattice::{Lattice, Square},
+    packed::{Packed, PackedPolyomino},
+    polyomino::{Mode, Polyomino, Window},
+    rotation::SymmetryClass,
+};
 
 /// The database holds three things:
 /// - the number of polyominoes with some square amount, if all have been processed
 /// - the queue of unprocessed polyominoes of the last square amount
 /// - the cache of polyominoes for the next amount
 /// - stats by square amount
-pub struct Database<T>
+/// - the number of polyominoes in each symmetry class, by square amount,
+///   if all have been processed
+/// - the number of polyominoes without holes, by square amount, if all have been processed
+///
+/// The mode tells which polyominoes are identified by [`Database::expand`].
+/// With a window, only the polyominoes fitting it are enumerated and counted.
+/// Polyominoes are stored packed, and only expanded into grids of type `T` to be processed.
+/// They are polyforms of the lattice `L`, the square lattice by default, whose symmetry
+/// classes are the only ones counted
+pub struct Database<T, L = Square>
 where
     T: Grid,
+    L: Lattice,
 {
+    mode: Mode,
+    window: Option<Window>,
     counts: Vec<u128>,
-    queue: VecDeque<Polyomino<T>>,
-    cache: BTreeMap<(u8, u8), HashMap<T, T>>,
+    queue: VecDeque<PackedPolyomino>,
+    cache: Cache,
     stats: Vec<u128>,
+    classes: Vec<Classes>,
+    hole_free: Vec<u128>,
+    grid: PhantomData<T>,
+    lattice: PhantomData<L>,
+}
+
+/// Magic number and version of the checkpoint files
+const CHECKPOINT_MAGIC: &[u8] = b"POLYDB";
+const CHECKPOINT_VERSION: u8 = 5;
+
+/// Magic number and version of the level files
+const LEVEL_MAGIC: &[u8] = b"POLYLV";
+const LEVEL_VERSION: u8 = 5;
+
+type Classes = [u128; SymmetryClass::ALL.len()];
+
+/// Magic number and version of the files of polyominoes routed between shards
+const ROUTE_MAGIC: &[u8] = b"POLYRT";
+const ROUTE_VERSION: u8 = 3;
+
+/// Masks of the polyominoes by dimension, then by representative
+type Cache = BTreeMap<(u8, u8), HashMap<Packed, Packed>>;
+
+/// Number of cache shards for each worker of [`Database::expand_level`]
+const SHARDS_PER_THREAD: usize = 16;
+/// Number of polyominoes a worker takes from the queue at once
+const CHUNK: usize = 64;
+
+fn mode_to_u8(mode: Mode) -> u8 {
+    match mode {
+        Mode::Fixed => 0,
+        Mode::OneSided => 1,
+        Mode::Free => 2,
+    }
+}
+
+fn mode_from_u8(mode: u8) -> io::Result<Mode> {
+    match mode {
+        0 => Ok(Mode::Fixed),
+        1 => Ok(Mode::OneSided),
+        2 => Ok(Mode::Free),
+        _ => Err(invalid_data("Unknown mode")),
+    }
+}
+
+/// An empty window stands for no window
+fn write_window<W: Write>(w: &mut W, window: Option<Window>) -> io::Result<()> {
+    let window = window.unwrap_or(Window {
+        width: 0,
+        height: 0,
+    });
+    write_u8(w, window.width)?;
+    write_u8(w, window.height)
+}
+
+fn read_window<R: Read>(r: &mut R) -> io::Result<Option<Window>> {
+    match (read_u8(r)?, read_u8(r)?) {
+        (0, 0) => Ok(None),
+        (0, _) | (_, 0) => Err(invalid_data("Empty window")),
+        (width, height) => Ok(Some(Window { width, height })),
+    }
+}
+
+/// The children of the polyomino that fit the window, if any
+fn children<T: Grid, L: Lattice>(
+    p: &Polyomino<T>,
+    mode: Mode,
+    window: Option<Window>,
+) -> Vec<Polyomino<T>> {
+    L::decline_within(p, |size| window.is_none_or(|w| w.fits(size, mode)))
+}
+
+/// The lattice is written after the mode in all files
+fn check_lattice<L: Lattice, R: Read>(r: &mut R) -> io::Result<()> {
+    if read_u8(r)? != L::ID {
+        return Err(invalid_data(&format!(
+            "The polyominoes are not on the {} lattice",
+            L::NAME
+        )));
+    }
+    Ok(())
+}
+
+fn write_u128s<W: Write>(w: &mut W, values: &[u128]) -> io::Result<()> {
+    write_u32(w, values.len() as u32)?;
+    for &v in values.iter() {
+        write_u128(w, v)?;
+    }
+    Ok(())
+}
+
+fn read_u128s<R: Read>(r: &mut R) -> io::Result<Vec<u128>> {
+    (0..read_u32(r)?).map(|_| read_u128(r)).collect()
+}
+
+fn write_classes<W: Write>(w: &mut W, classes: &[Classes]) -> io::Result<()> {
+    write_u32(w, classes.len() as u32)?;
+    for level in classes.iter() {
+        for &count in level.iter() {
+            write_u128(w, count)?;
+        }
+    }
+    Ok(())
+}
+
+fn read_classes<R: Read>(r: &mut R) -> io::Result<Vec<Classes>> {
+    let mut classes = vec![];
+    for _ in 0..read_u32(r)? {
+        let mut level = [0; SymmetryClass::ALL.len()];
+        for count in level.iter_mut() {
+            *count = read_u128(r)?;
+        }
+        classes.push(level);
+    }
+    Ok(classes)
 }
 
 fn treemap_get_mut_or<K, V, F>(map: &mut BTreeMap<K, V>, key: K, f: F) -> &mut V
@@ -27,11 +168,7 @@ where
     K: Ord + Copy,
     F: Fn() -> V,
 {
-    if !map.contains_key(&key) {
-        map.insert(key, f());
-    }
-
-    map.get_mut(&key).unwrap()
+    map.entry(key).or_insert_with(f)
 }
 
 /// Returns None if the value is added, returns the value already stored otherwise
@@ -39,64 +176,216 @@ fn hashmap_get_mut_or<K, V>(map: &mut HashMap<K, V>, key: K, v: V) -> Option<&mu
 where
     K: Ord + Hash,
 {
-    if !map.contains_key(&key) {
-        map.insert(key, v);
-        None
-    } else {
-        map.get_mut(&key)
+    match map.entry(key) {
+        Entry::Vacant(entry) => {
+            entry.insert(v);
+            None
+        }
+        Entry::Occupied(entry) => Some(entry.into_mut()),
+    }
+}
+
+/// Add the polyomino to the cache, merging its mask with the one of the
+/// same polyomino if it is already known
+fn insert_or_merge(cache: &mut Cache, p: PackedPolyomino) {
+    let map = treemap_get_mut_or(cache, p.dimension, HashMap::new);
+    if let Some(mask) = hashmap_get_mut_or(map, p.repr, p.mask) {
+        *mask |= p.mask
+    }
+}
+
+/// The shard owning the polyominoes of dimension `dim`, among `shards` shards.
+/// Adding a square keeps the dimension or grows it by one in a direction, so
+/// the children of a polyomino are spread among a few shards
+pub fn owner(dim: (u8, u8), shards: usize) -> usize {
+    (dim.0 as usize * 32 + dim.1 as usize) % shards
+}
+
+fn shard_of<T: Hash>(repr: &T, shards: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    repr.hash(&mut hasher);
+    (hasher.finish() % shards as u64) as usize
+}
+
+impl<T, L> Default for Database<T, L>
+where
+    T: Grid + Ord + Hash + BitOrAssign,
+    L: Lattice,
+{
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl<T> Database<T>
 where
     T: Grid + Ord + Hash + BitOrAssign,
+{
+    /// Only enumerate the polyominoes fitting the window, in one of the orientations
+    /// allowed by the mode. Polyominoes outside of it are pruned when declining their
+    /// parents, so the work only depends on the polyominoes which fit
+    pub fn bounded(mode: Mode, window: Window) -> Self {
+        Self {
+            window: Some(window),
+            ..Self::with_mode(mode)
+        }
+    }
+}
+
+impl<T, L> Database<T, L>
+where
+    T: Grid + Ord + Hash + BitOrAssign,
+    L: Lattice,
 {
     pub fn new() -> Self {
-        let mut queue = VecDeque::new();
-        queue.push_back(Polyomino::trivial());
+        Self::with_mode(Mode::OneSided)
+    }
+
+    /// Start from the polyforms with one cell
+    pub fn with_mode(mode: Mode) -> Self {
+        let seeds = L::seeds::<T>(mode);
+        let mut classes = [0; SymmetryClass::ALL.len()];
+        for seed in seeds.iter() {
+            if let Some(class) = L::symmetry_class(seed) {
+                classes[class as usize] += 1;
+            }
+        }
+        let count = seeds.len() as u128;
 
         Self {
-            counts: vec![1],
-            queue,
+            mode,
+            window: None,
+            counts: vec![count],
+            queue: seeds.iter().map(PackedPolyomino::pack).collect(),
             cache: BTreeMap::new(),
-            stats: vec![1, 0],
+            stats: vec![count, 0],
+            classes: vec![classes],
+            hole_free: vec![count],
+            grid: PhantomData,
+            lattice: PhantomData,
         }
     }
 
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn window(&self) -> Option<Window> {
+        self.window
+    }
+
     pub fn to_queue(self) -> VecDeque<Polyomino<T>> {
-        self.queue
+        self.queue.into_iter().map(|p| p.unpack()).collect()
     }
 
     pub fn pop(&mut self) -> Option<Polyomino<T>> {
-        self.queue.pop_front()
+        self.queue.pop_front().map(|p| p.unpack())
     }
 
-    /// Register the polyomino in the cache
+    /// Register the polyomino in the cache. The masks of the same polyomino are merged
+    /// with a union, so the merged mask does not depend on the order of registration
     pub fn register(&mut self, p: Polyomino<T>) {
-        let mut map = treemap_get_mut_or(&mut self.cache, p.dimension, || HashMap::new());
-
         *self.stats.last_mut().unwrap() += 1;
+        insert_or_merge(&mut self.cache, PackedPolyomino::pack(&p));
+    }
 
-        match hashmap_get_mut_or(&mut map, p.repr, p.mask.clone()) {
-            Some(mask) => *mask |= p.mask,
-            None => (),
+    /// Return the polyominoes obtained by adding a square to the given one which are
+    /// expanded by the database, that is the ones fitting its window if any,
+    /// before they are brought to their representative
+    pub fn children(&self, p: &Polyomino<T>) -> Vec<Polyomino<T>> {
+        children::<T, L>(p, self.mode, self.window)
+    }
+
+    /// Register all polyominoes obtained by adding a square to the given one,
+    /// once brought to their representative for the mode of the database
+    pub fn expand(&mut self, p: &Polyomino<T>) {
+        for p in self.children(p).into_iter() {
+            self.register(L::canonical(p, self.mode).0);
         }
     }
 
+    /// Expand all polyominoes left in the queue with `threads` workers, then flush.
+    /// The workers deduplicate the new polyominoes in caches sharded by the hash of
+    /// their representative, which are merged into the cache once they are all joined,
+    /// so the counts and masks are the same as with [`Database::expand`].
+    /// The queue is drained before the workers start, and flushed after they are done
+    pub fn expand_level(&mut self, threads: usize) {
+        let queue = Vec::from(take(&mut self.queue));
+        let shards: Vec<Mutex<Cache>> = (0..threads.max(1) * SHARDS_PER_THREAD)
+            .map(|_| Mutex::default())
+            .collect();
+        let next = AtomicUsize::new(0);
+        let (mode, window) = (self.mode, self.window);
+
+        let registered: u128 = thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads.max(1))
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut registered = 0;
+                        loop {
+                            let start = next.fetch_add(CHUNK, Ordering::Relaxed);
+                            if start >= queue.len() {
+                                return registered;
+                            }
+                            let end = (start + CHUNK).min(queue.len());
+                            for p in queue[start..end].iter() {
+                                let p = p.unpack::<T>();
+                                for p in children::<T, L>(&p, mode, window).into_iter() {
+                                    let p = PackedPolyomino::pack(&L::canonical(p, mode).0);
+                                    let shard = &shards[shard_of(&p.repr, shards.len())];
+                                    insert_or_merge(&mut shard.lock().unwrap(), p);
+                                    registered += 1;
+                                }
+                            }
+                        }
+                    })
+                })
+                .collect();
+            workers.into_iter().map(|w| w.join().unwrap()).sum()
+        });
+
+        *self.stats.last_mut().unwrap() += registered;
+        for shard in shards.into_iter() {
+            for (dim, map) in shard.into_inner().unwrap().into_iter() {
+                let target = treemap_get_mut_or(&mut self.cache, dim, HashMap::new);
+                for (repr, mask) in map.into_iter() {
+                    if let Some(stored) = hashmap_get_mut_or(target, repr, mask) {
+                        *stored |= mask
+                    }
+                }
+            }
+        }
+        self.flush();
+    }
+
     /// Flush the cache into the queue, ready to start processing the new polyominoes with a new square,
     /// also adding the number of polyominoes to the database.
     /// Panics if the queue is not empty.
     /// WARNING: if another process picks the last element of the queue and flush is called before
-    /// this last element was processed and registered to the database, the count could be wrong
+    /// this last element was processed and registered to the database, the count could be wrong.
+    /// Use [`Database::expand_level`] to process a level with several threads
     pub fn flush(&mut self) {
         if !self.queue.is_empty() {
             panic!("The queue database is not empty!")
         }
 
-        let cache = replace(&mut self.cache, BTreeMap::new());
+        let mut classes = [0; SymmetryClass::ALL.len()];
+        let mut hole_free = 0;
+        let cache = take(&mut self.cache);
         for (dim, hashmap) in cache.into_iter() {
             for (repr, mask) in hashmap.into_iter() {
-                let p = Polyomino::from(dim, repr, mask);
+                let p = PackedPolyomino {
+                    dimension: dim,
+                    repr,
+                    mask,
+                };
+                let unpacked = p.unpack::<T>();
+                if let Some(class) = L::symmetry_class(&unpacked) {
+                    classes[class as usize] += 1;
+                }
+                if !L::has_holes(&unpacked) {
+                    hole_free += 1;
+                }
 
                 // println!("Flushing:");
                 // println!("{p:?}");
@@ -107,6 +396,276 @@ where
 
         self.counts.push(self.queue.len() as u128);
         self.stats.push(0);
+        self.classes.push(classes);
+        self.hole_free.push(hole_free);
+    }
+
+    /// Write the whole state of the database, such that it can be restored
+    /// with [`Database::load`]. The state must be saved between two calls to
+    /// [`Database::expand`].
+    /// The file does not depend on the grid backend, as all backends pick the same
+    /// representatives, so a database can be restored with another backend
+    pub fn save<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        write_header(w, CHECKPOINT_MAGIC, CHECKPOINT_VERSION)?;
+        write_u8(w, mode_to_u8(self.mode))?;
+        write_u8(w, L::ID)?;
+        write_window(w, self.window)?;
+
+        write_u128s(w, &self.counts)?;
+        write_u128s(w, &self.stats)?;
+        write_classes(w, &self.classes)?;
+        write_u128s(w, &self.hole_free)?;
+
+        write_u64(w, self.queue.len() as u64)?;
+        for p in self.queue.iter() {
+            p.write(w)?;
+        }
+
+        write_u32(w, self.cache.len() as u32)?;
+        for (&dim, map) in self.cache.iter() {
+            write_u8(w, dim.0)?;
+            write_u8(w, dim.1)?;
+            write_u64(w, map.len() as u64)?;
+            for (repr, mask) in map.iter() {
+                repr.write(w, dim)?;
+                mask.write(w, dim)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Restore a database written by [`Database::save`]
+    pub fn load<R: Read>(r: &mut R) -> io::Result<Self> {
+        read_header(r, CHECKPOINT_MAGIC, CHECKPOINT_VERSION)?;
+        let mode = mode_from_u8(read_u8(r)?)?;
+        check_lattice::<L, _>(r)?;
+        let window = read_window(r)?;
+
+        let counts = read_u128s(r)?;
+        let stats = read_u128s(r)?;
+        let classes = read_classes(r)?;
+        let hole_free = read_u128s(r)?;
+
+        let queue = (0..read_u64(r)?)
+            .map(|_| PackedPolyomino::read(r))
+            .collect::<io::Result<_>>()?;
+
+        let mut cache = BTreeMap::new();
+        for _ in 0..read_u32(r)? {
+            let dim = (read_u8(r)?, read_u8(r)?);
+            let mut map = HashMap::new();
+            for _ in 0..read_u64(r)? {
+                let repr = Packed::read(r, dim)?;
+                let mask = Packed::read(r, dim)?;
+                map.insert(repr, mask);
+            }
+            cache.insert(dim, map);
+        }
+
+        Ok(Self {
+            mode,
+            window,
+            counts,
+            queue,
+            cache,
+            stats,
+            classes,
+            hole_free,
+            grid: PhantomData,
+            lattice: PhantomData,
+        })
+    }
+
+    /// Write the last completed level: all polyominoes of the queue, with
+    /// the counts of the previous levels. The database must have just been flushed.
+    /// The level can be loaded with [`Database::from_level`] into any grid backend
+    pub fn save_level<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.check_level_complete()?;
+
+        write_header(w, LEVEL_MAGIC, LEVEL_VERSION)?;
+        write_u8(w, mode_to_u8(self.mode))?;
+        write_u8(w, L::ID)?;
+        write_window(w, self.window)?;
+        write_u128s(w, &self.counts)?;
+        // The last stat is for the level in progress
+        write_u128s(w, &self.stats[0..self.counts.len()])?;
+        write_classes(w, &self.classes)?;
+        write_u128s(w, &self.hole_free)?;
+
+        write_u64(w, self.queue.len() as u64)?;
+        for p in self.queue.iter() {
+            p.write(w)?;
+        }
+
+        Ok(())
+    }
+
+    fn check_level_complete(&self) -> io::Result<()> {
+        if !self.cache.is_empty() || self.queue.len() as u128 != *self.counts.last().unwrap() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The last level is not complete or has already been processed",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Split the last level between `shards` databases, according to [`owner`].
+    /// The database must have just been flushed. The counts, stats and symmetry classes
+    /// of the shards add up to the ones of the database, the previous levels being
+    /// attributed to the first shard
+    pub fn split(self, shards: usize) -> io::Result<Vec<Self>> {
+        self.check_level_complete()?;
+
+        let previous = self.counts.len() - 1;
+        let mut parts: Vec<_> = (0..shards)
+            .map(|k| {
+                let mut counts = vec![0; previous];
+                let mut stats = vec![0; self.stats.len()];
+                let mut classes = vec![[0; SymmetryClass::ALL.len()]; previous];
+                let mut hole_free = vec![0; previous];
+                if k == 0 {
+                    counts.copy_from_slice(&self.counts[..previous]);
+                    stats.copy_from_slice(&self.stats);
+                    classes.copy_from_slice(&self.classes[..previous]);
+                    hole_free.copy_from_slice(&self.hole_free[..previous]);
+                }
+                counts.push(0);
+                classes.push([0; SymmetryClass::ALL.len()]);
+                hole_free.push(0);
+                Self {
+                    mode: self.mode,
+                    window: self.window,
+                    counts,
+                    queue: VecDeque::new(),
+                    cache: BTreeMap::new(),
+                    stats,
+                    classes,
+                    hole_free,
+                    grid: PhantomData,
+                    lattice: PhantomData,
+                }
+            })
+            .collect();
+
+        for p in self.queue.into_iter() {
+            let part = &mut parts[owner(p.dimension, shards)];
+            let unpacked = p.unpack::<T>();
+            *part.counts.last_mut().unwrap() += 1;
+            if let Some(class) = L::symmetry_class(&unpacked) {
+                part.classes.last_mut().unwrap()[class as usize] += 1;
+            }
+            if !L::has_holes(&unpacked) {
+                *part.hole_free.last_mut().unwrap() += 1;
+            }
+            part.queue.push_back(p);
+        }
+        Ok(parts)
+    }
+
+    /// Expand all polyominoes of the queue into the cache, like [`Database::expand`],
+    /// then write the representatives with their merged masks to the outbox of the shard
+    /// owning their dimension, see [`owner`], emptying the cache. The duplicates met by
+    /// this shard are counted in its stats, and the polyominoes by their owner in
+    /// [`Database::receive`]
+    pub fn route<W: Write>(&mut self, outboxes: &mut [W]) -> io::Result<()> {
+        for w in outboxes.iter_mut() {
+            write_header(w, ROUTE_MAGIC, ROUTE_VERSION)?;
+            write_u8(w, mode_to_u8(self.mode))?;
+            write_u8(w, L::ID)?;
+        }
+
+        while let Some(p) = self.pop() {
+            self.expand(&p);
+        }
+        let shards = outboxes.len();
+        for (dimension, map) in take(&mut self.cache).into_iter() {
+            let w = &mut outboxes[owner(dimension, shards)];
+            for (repr, mask) in map.into_iter() {
+                PackedPolyomino {
+                    dimension,
+                    repr,
+                    mask,
+                }
+                .write(w)?;
+            }
+        }
+
+        // An empty dimension marks the end, to detect truncated files
+        for w in outboxes.iter_mut() {
+            write_u8(w, 0)?;
+            write_u8(w, 0)?;
+        }
+        Ok(())
+    }
+
+    /// Merge all polyominoes written by [`Database::route`] into the cache. They were
+    /// already counted as registered by the shard routing them
+    pub fn receive<R: Read>(&mut self, r: &mut R) -> io::Result<()> {
+        read_header(r, ROUTE_MAGIC, ROUTE_VERSION)?;
+        if mode_from_u8(read_u8(r)?)? != self.mode {
+            return Err(invalid_data(
+                "The polyominoes were routed with another mode",
+            ));
+        }
+        check_lattice::<L, _>(r)?;
+
+        loop {
+            let dimension = (read_u8(r)?, read_u8(r)?);
+            if dimension == (0, 0) {
+                return Ok(());
+            }
+            let p = PackedPolyomino {
+                dimension,
+                repr: Packed::read(r, dimension)?,
+                mask: Packed::read(r, dimension)?,
+            };
+            insert_or_merge(&mut self.cache, p);
+        }
+    }
+
+    /// Return the mode of a level written by [`Database::save_level`],
+    /// without reading its polyominoes
+    pub fn level_mode<R: Read>(r: &mut R) -> io::Result<Mode> {
+        read_header(r, LEVEL_MAGIC, LEVEL_VERSION)?;
+        let mode = mode_from_u8(read_u8(r)?)?;
+        check_lattice::<L, _>(r)?;
+        Ok(mode)
+    }
+
+    /// Create a database from a level written by [`Database::save_level`],
+    /// ready to compute the next level
+    pub fn from_level<R: Read>(r: &mut R) -> io::Result<Self> {
+        read_header(r, LEVEL_MAGIC, LEVEL_VERSION)?;
+        let mode = mode_from_u8(read_u8(r)?)?;
+        check_lattice::<L, _>(r)?;
+        let window = read_window(r)?;
+        let counts = read_u128s(r)?;
+        let mut stats = read_u128s(r)?;
+        stats.push(0);
+        let classes = read_classes(r)?;
+        let hole_free = read_u128s(r)?;
+
+        let queue: VecDeque<_> = (0..read_u64(r)?)
+            .map(|_| PackedPolyomino::read(r))
+            .collect::<io::Result<_>>()?;
+        if counts.last() != Some(&(queue.len() as u128)) {
+            return Err(invalid_data("The level does not match its count"));
+        }
+
+        Ok(Self {
+            mode,
+            window,
+            counts,
+            queue,
+            cache: BTreeMap::new(),
+            stats,
+            classes,
+            hole_free,
+            grid: PhantomData,
+            lattice: PhantomData,
+        })
     }
 
     /// Returns Some number of polyominoes with [n] squares,
@@ -124,7 +683,607 @@ where
         self.counts.iter()
     }
 
+    /// Return the number of polyominoes of the last level by dimension of their
+    /// bounding box, without the margin. Representatives identified up to rotations
+    /// have at most as many columns as rows. The database must have just been flushed
+    pub fn counts_by_size(&self) -> io::Result<BTreeMap<(usize, usize), u128>> {
+        self.check_level_complete()?;
+
+        let mut sizes = BTreeMap::new();
+        for p in self.queue.iter() {
+            let size = (p.dimension.0 as usize - 2, p.dimension.1 as usize - 2);
+            *sizes.entry(size).or_insert(0) += 1;
+        }
+        Ok(sizes)
+    }
+
     pub fn stats(&self) -> std::slice::Iter<'_, u128> {
         self.stats.iter()
     }
+
+    /// Return an iterator on the number of polyominoes in each symmetry class,
+    /// indexed in the order of [`SymmetryClass::ALL`]
+    pub fn classes(&self) -> std::slice::Iter<'_, Classes> {
+        self.classes.iter()
+    }
+
+    /// Return an iterator on the number of polyominoes without holes
+    pub fn hole_free(&self) -> std::slice::Iter<'_, u128> {
+        self.hole_free.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        board::Board,
+        grids::{block_grid::BlockGrid, naive::Naive},
+        lattice::{Hexagonal, Triangular},
+        polyomino::{canonical, decline, decline_in_order},
+    };
+
+    use super::*;
+
+    fn run<T>(mode: Mode, limit: usize) -> Database<T>
+    where
+        T: Grid + BitOrAssign,
+    {
+        let mut db = Database::<T>::with_mode(mode);
+        while db.counts().len() < limit {
+            match db.pop() {
+                None => db.flush(),
+                Some(p) => db.expand(&p),
+            }
+        }
+        db
+    }
+
+    fn counts<T>(mode: Mode, limit: usize) -> Vec<u128>
+    where
+        T: Grid + BitOrAssign,
+    {
+        run::<T>(mode, limit).counts().copied().collect()
+    }
+
+    #[test]
+    fn one_sided_counts() {
+        let expected = [1, 1, 2, 7, 18, 60, 196, 704];
+        assert_eq!(counts::<Naive>(Mode::OneSided, 8), expected);
+        assert_eq!(counts::<BlockGrid>(Mode::OneSided, 8), expected);
+        assert_eq!(counts::<Board>(Mode::OneSided, 8), expected);
+    }
+
+    #[test]
+    fn fixed_counts() {
+        let expected = [1, 2, 6, 19, 63, 216, 760, 2725];
+        assert_eq!(counts::<Naive>(Mode::Fixed, 8), expected);
+        assert_eq!(counts::<BlockGrid>(Mode::Fixed, 8), expected);
+        assert_eq!(counts::<Board>(Mode::Fixed, 8), expected);
+    }
+
+    #[test]
+    fn free_counts() {
+        let expected = [1, 1, 2, 5, 12, 35, 108, 369];
+        assert_eq!(counts::<Naive>(Mode::Free, 8), expected);
+        assert_eq!(counts::<BlockGrid>(Mode::Free, 8), expected);
+        assert_eq!(counts::<Board>(Mode::Free, 8), expected);
+    }
+
+    /// The polyominoes of the queue with their masks, in an order independent of the processing
+    fn sorted_queue<T>(db: Database<T>) -> Vec<((u8, u8), T, T)>
+    where
+        T: Grid + BitOrAssign,
+    {
+        let mut queue = db
+            .to_queue()
+            .into_iter()
+            .map(|p| (p.dimension, p.repr, p.mask))
+            .collect::<Vec<_>>();
+        queue.sort();
+        queue
+    }
+
+    /// A xorshift generator, for reproducible shuffles
+    fn random() -> impl FnMut() -> u64 {
+        let mut seed = 0x9e37_79b9_7f4a_7c15u64;
+        move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        }
+    }
+
+    fn shuffle<V>(values: &mut [V], random: &mut impl FnMut() -> u64) {
+        for i in (1..values.len()).rev() {
+            values.swap(i, random() as usize % (i + 1));
+        }
+    }
+
+    #[test]
+    fn shuffled_queue() {
+        let mut random = random();
+        for mode in [Mode::Fixed, Mode::OneSided, Mode::Free] {
+            let expected = run::<Board>(mode, 8);
+
+            // Shuffle each level, and the children of each polyomino
+            let mut db = Database::<Board>::with_mode(mode);
+            while db.counts().len() < 8 {
+                let mut queue = Vec::from(take(&mut db.queue));
+                shuffle(&mut queue, &mut random);
+                for p in queue.iter() {
+                    let mut children = decline(&p.unpack::<Board>());
+                    shuffle(&mut children, &mut random);
+                    for child in children.into_iter() {
+                        db.register(canonical(child, mode).0);
+                    }
+                }
+                db.flush();
+            }
+
+            assert!(db.counts().eq(expected.counts()));
+            assert!(db.stats().eq(expected.stats()));
+            assert_eq!(sorted_queue(db), sorted_queue(expected));
+        }
+    }
+
+    #[test]
+    fn shuffled_mask_order() {
+        let mut random = random();
+        for mode in [Mode::Fixed, Mode::OneSided, Mode::Free] {
+            let expected = run::<BlockGrid>(mode, 9);
+
+            // Add the squares of the mask in a random order, which changes the masks
+            // of the children, but not the polyominoes enumerated from them
+            let mut db = Database::<BlockGrid>::with_mode(mode);
+            let mut masks_differ = false;
+            while db.counts().len() < 9 {
+                let Some(p) = db.pop() else {
+                    db.flush();
+                    continue;
+                };
+                let mut order = vec![];
+                for x in 0..p.dimension.0 as usize {
+                    for y in 0..p.dimension.1 as usize {
+                        if p.mask.get(x, y) {
+                            order.push((x, y));
+                        }
+                    }
+                }
+                shuffle(&mut order, &mut random);
+                let children = decline_in_order(&p, |_| true, &order);
+                let in_order = decline(&p);
+                masks_differ |= children.iter().any(|child| {
+                    in_order
+                        .iter()
+                        .any(|other| other.repr == child.repr && other.mask != child.mask)
+                });
+                for child in children.into_iter() {
+                    db.register(canonical(child, mode).0);
+                }
+            }
+
+            assert!(masks_differ);
+            assert!(db.counts().eq(expected.counts()));
+            assert!(db.classes().eq(expected.classes()));
+        }
+    }
+
+    #[test]
+    fn parallel_expansion() {
+        for mode in [Mode::Fixed, Mode::OneSided, Mode::Free] {
+            let expected = run::<BlockGrid>(mode, 8);
+
+            let mut db = Database::<BlockGrid>::with_mode(mode);
+            while db.counts().len() < 8 {
+                db.expand_level(4);
+            }
+
+            assert!(db.counts().eq(expected.counts()));
+            assert!(db.stats().eq(expected.stats()));
+            assert!(db.classes().eq(expected.classes()));
+            assert_eq!(sorted_queue(db), sorted_queue(expected));
+        }
+    }
+
+    #[test]
+    fn parallel_expansion_after_sequential_start() {
+        let expected = run::<Board>(Mode::Free, 8);
+
+        let mut db = run::<Board>(Mode::Free, 7);
+        for _ in 0..50 {
+            let p = db.pop().unwrap();
+            db.expand(&p);
+        }
+        db.expand_level(3);
+
+        assert!(db.counts().eq(expected.counts()));
+        assert!(db.stats().eq(expected.stats()));
+        assert_eq!(sorted_queue(db), sorted_queue(expected));
+    }
+
+    /// Sum the counts, stats and symmetry classes of the shards
+    fn sum_shards<T>(shards: &[Database<T>]) -> (Vec<u128>, Vec<u128>, Vec<Classes>)
+    where
+        T: Grid + BitOrAssign,
+    {
+        let mut counts = vec![0; shards[0].counts.len()];
+        let mut stats = vec![0; shards[0].stats.len()];
+        let mut classes = vec![[0; SymmetryClass::ALL.len()]; shards[0].classes.len()];
+        for shard in shards.iter() {
+            counts
+                .iter_mut()
+                .zip(shard.counts())
+                .for_each(|(a, b)| *a += b);
+            stats
+                .iter_mut()
+                .zip(shard.stats())
+                .for_each(|(a, b)| *a += b);
+            for (a, b) in classes.iter_mut().zip(shard.classes()) {
+                a.iter_mut().zip(b.iter()).for_each(|(a, b)| *a += b);
+            }
+        }
+        (counts, stats, classes)
+    }
+
+    #[test]
+    fn sharded_enumeration() {
+        let expected = run::<BlockGrid>(Mode::Free, 9);
+        let shards = 3;
+
+        let mut dbs = run::<BlockGrid>(Mode::Free, 5).split(shards).unwrap();
+        assert_eq!(
+            sum_shards(&dbs).0,
+            expected.counts().take(5).copied().collect::<Vec<_>>()
+        );
+
+        while dbs[0].counts().len() < 9 {
+            let mut outboxes = vec![vec![vec![]; shards]; shards];
+            for (db, outboxes) in dbs.iter_mut().zip(outboxes.iter_mut()) {
+                db.route(outboxes).unwrap();
+            }
+            for (k, db) in dbs.iter_mut().enumerate() {
+                for outboxes in outboxes.iter() {
+                    db.receive(&mut outboxes[k].as_slice()).unwrap();
+                }
+                db.flush();
+                for p in db.queue.iter() {
+                    assert_eq!(owner(p.dimension, shards), k);
+                }
+            }
+        }
+
+        let (counts, stats, classes) = sum_shards(&dbs);
+        assert!(counts.iter().eq(expected.counts()));
+        assert!(stats.iter().eq(expected.stats()));
+        assert!(classes.iter().eq(expected.classes()));
+        let hole_free = (0..9).map(|n| dbs.iter().map(|db| db.hole_free[n]).sum::<u128>());
+        assert!(hole_free.eq(expected.hole_free().copied()));
+    }
+
+    /// The number of polyominoes in a file written by [`Database::route`]
+    fn routed(mut r: &[u8]) -> usize {
+        read_header(&mut r, ROUTE_MAGIC, ROUTE_VERSION).unwrap();
+        let _mode_and_lattice = (read_u8(&mut r).unwrap(), read_u8(&mut r).unwrap());
+        let mut count = 0;
+        loop {
+            let dimension = (read_u8(&mut r).unwrap(), read_u8(&mut r).unwrap());
+            if dimension == (0, 0) {
+                return count;
+            }
+            Packed::read(&mut r, dimension).unwrap();
+            Packed::read(&mut r, dimension).unwrap();
+            count += 1;
+        }
+    }
+
+    #[test]
+    fn routed_without_duplicates() {
+        let expected = run::<Board>(Mode::Free, 8);
+        let mut dbs = run::<Board>(Mode::Free, 7).split(2).unwrap();
+        let mut outboxes = vec![vec![vec![]; 2]; 2];
+        for (db, outboxes) in dbs.iter_mut().zip(outboxes.iter_mut()) {
+            db.route(outboxes).unwrap();
+        }
+
+        let sent: usize = outboxes.iter().flatten().map(|outbox| routed(outbox)).sum();
+        for (k, db) in dbs.iter_mut().enumerate() {
+            for outboxes in outboxes.iter() {
+                db.receive(&mut outboxes[k].as_slice()).unwrap();
+            }
+            db.flush();
+        }
+        let counts: u128 = dbs.iter().map(|db| db.counts().nth(7).unwrap()).sum();
+        let stats: u128 = dbs.iter().map(|db| db.stats().nth(7).unwrap()).sum();
+        assert_eq!(Some(&counts), expected.counts().nth(7));
+        assert_eq!(Some(&stats), expected.stats().nth(7));
+        // Each shard sends each of its new polyominoes once, not each registration
+        assert!((sent as u128) < stats);
+
+        // With a single shard, the routed file holds the next level
+        let mut db = run::<Board>(Mode::Free, 7);
+        let mut outbox = vec![vec![]];
+        db.route(&mut outbox).unwrap();
+        assert_eq!(routed(&outbox[0]) as u128, counts);
+    }
+
+    #[test]
+    fn receive_truncated() {
+        let mut db = run::<Naive>(Mode::OneSided, 4);
+        let mut outbox = vec![vec![]];
+        db.route(&mut outbox).unwrap();
+        db.receive(&mut outbox[0].as_slice()).unwrap();
+
+        outbox[0].pop();
+        assert!(db.receive(&mut outbox[0].as_slice()).is_err());
+    }
+
+    #[test]
+    fn free_symmetry_classes() {
+        // Columns ordered as in SymmetryClass::ALL
+        let expected = [
+            [0, 0, 0, 0, 0, 0, 0, 1],
+            [0, 0, 0, 0, 1, 0, 0, 0],
+            [0, 0, 1, 0, 1, 0, 0, 0],
+            [1, 1, 0, 1, 1, 0, 0, 1],
+            [5, 2, 2, 1, 1, 0, 0, 1],
+            [20, 6, 2, 5, 2, 0, 0, 0],
+            [84, 9, 7, 4, 3, 1, 0, 0],
+            [316, 23, 5, 18, 4, 1, 1, 1],
+        ];
+        let db = run::<BlockGrid>(Mode::Free, 8);
+        assert_eq!(db.classes().copied().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn hole_free_counts() {
+        let free = run::<BlockGrid>(Mode::Free, 9);
+        assert!(free.hole_free().eq(&[1, 1, 2, 5, 12, 35, 107, 363, 1248]));
+
+        // The only heptomino with a hole has a mirror axis, and four fixed versions
+        let one_sided = run::<Board>(Mode::OneSided, 7);
+        assert_eq!(one_sided.hole_free().last(), Some(&195));
+        let fixed = run::<Naive>(Mode::Fixed, 7);
+        assert_eq!(fixed.hole_free().last(), Some(&756));
+    }
+
+    #[test]
+    fn burnside() {
+        // A free polyomino with a stabilizer of order k has 8 / k fixed versions,
+        // and 2 one-sided versions if it has no mirror axis
+        let fixed = counts::<Naive>(Mode::Fixed, 7);
+        let one_sided = counts::<Naive>(Mode::OneSided, 7);
+        let db = run::<Naive>(Mode::Free, 7);
+        for (n, classes) in db.classes().enumerate() {
+            let mut fixed_from_free = 0;
+            let mut one_sided_from_free = 0;
+            for (class, count) in SymmetryClass::ALL.iter().zip(classes.iter()) {
+                fixed_from_free += count * (8 / class.order() as u128);
+                one_sided_from_free += match class {
+                    SymmetryClass::Asymmetric | SymmetryClass::Rot2 | SymmetryClass::Rot4 => 2,
+                    _ => 1,
+                } * count;
+            }
+            assert_eq!(fixed_from_free, fixed[n]);
+            assert_eq!(one_sided_from_free, one_sided[n]);
+        }
+    }
+
+    #[test]
+    fn bounded_enumeration() {
+        for mode in [Mode::Fixed, Mode::OneSided, Mode::Free] {
+            for window in [(3, 4), (4, 3), (2, 8), (5, 5)] {
+                let window = Window {
+                    width: window.0,
+                    height: window.1,
+                };
+                let mut bounded = Database::<BlockGrid>::bounded(mode, window);
+                let mut full = Database::<BlockGrid>::with_mode(mode);
+                while full.counts().len() < 8 {
+                    bounded.expand_level(2);
+                    full.expand_level(2);
+
+                    // The same polyominoes as the ones of the full level which fit
+                    let mut expected = full.counts_by_size().unwrap();
+                    expected.retain(|&size, _| window.fits(size, mode));
+                    assert_eq!(bounded.counts_by_size().unwrap(), expected);
+                    assert_eq!(
+                        bounded.counts().last(),
+                        Some(&expected.values().sum::<u128>())
+                    );
+                }
+                assert!(bounded.stats().sum::<u128>() < full.stats().sum::<u128>());
+            }
+        }
+    }
+
+    #[test]
+    fn bounded_children() {
+        let window = Window {
+            width: 3,
+            height: 3,
+        };
+        let expected = {
+            let mut db = Database::<Naive>::bounded(Mode::Free, window);
+            while db.counts().len() < 6 {
+                db.expand_level(1);
+            }
+            db
+        };
+
+        // Registering the children by hand, like the verification of the command line,
+        // keeps to the window
+        let mut db = Database::<Naive>::bounded(Mode::Free, window);
+        while db.counts().len() < 6 {
+            match db.pop() {
+                None => db.flush(),
+                Some(p) => {
+                    for child in db.children(&p) {
+                        db.register(canonical(child, Mode::Free).0);
+                    }
+                }
+            }
+        }
+        assert!(db.counts().eq(expected.counts()));
+        assert_eq!(db.counts().last(), Some(&8));
+    }
+
+    #[test]
+    fn bounded_by_size() {
+        // Representatives are turned to have at most as many columns as rows
+        let db = run::<Naive>(Mode::OneSided, 5);
+        let mut straight = db.counts_by_size().unwrap();
+        straight.retain(|&(width, height), _| width == 1 || height == 1);
+        assert_eq!(straight.into_iter().collect::<Vec<_>>(), [((1, 5), 1)]);
+
+        // Rotations are only allowed when the mode identifies them
+        let fits = |mode| {
+            let window = Window {
+                width: 3,
+                height: 2,
+            };
+            let mut db = Database::<Board>::bounded(mode, window);
+            while db.counts().len() < 4 {
+                db.expand_level(1);
+            }
+            db.counts().copied().collect::<Vec<_>>()
+        };
+        assert_eq!(fits(Mode::Fixed), [1, 2, 5, 9]);
+        assert_eq!(fits(Mode::OneSided), [1, 1, 2, 6]);
+        assert_eq!(fits(Mode::Free), [1, 1, 2, 4]);
+    }
+
+    #[test]
+    fn save_and_resume() {
+        let expected = run::<BlockGrid>(Mode::Free, 8);
+
+        // Interrupt the run in the middle of a level
+        let mut db = Database::<BlockGrid>::with_mode(Mode::Free);
+        while db.counts().len() < 7 {
+            match db.pop() {
+                None => db.flush(),
+                Some(p) => db.expand(&p),
+            }
+        }
+        for _ in 0..50 {
+            let p = db.pop().unwrap();
+            db.expand(&p);
+        }
+        let mut checkpoint = vec![];
+        db.save(&mut checkpoint).unwrap();
+
+        let mut db = Database::<BlockGrid>::load(&mut checkpoint.as_slice()).unwrap();
+        assert_eq!(db.mode(), Mode::Free);
+        assert_eq!(db.window(), None);
+        while db.counts().len() < 8 {
+            match db.pop() {
+                None => db.flush(),
+                Some(p) => db.expand(&p),
+            }
+        }
+
+        assert!(db.counts().eq(expected.counts()));
+        assert!(db.stats().eq(expected.stats()));
+        assert!(db.classes().eq(expected.classes()));
+        assert!(db.hole_free().eq(expected.hole_free()));
+    }
+
+    #[test]
+    fn resume_with_another_backend() {
+        let expected = run::<Naive>(Mode::OneSided, 8);
+
+        // Halfway through a level, the polyominoes already registered are the
+        // representatives the other backend would pick
+        let mut checkpoint = vec![];
+        let mut db = run::<BlockGrid>(Mode::OneSided, 6);
+        for _ in 0..db.count(6).map_or(0, |&count| count / 2) {
+            let p = db.pop().unwrap();
+            db.expand(&p);
+        }
+        db.save(&mut checkpoint).unwrap();
+        let mut db = Database::<Naive>::load(&mut checkpoint.as_slice()).unwrap();
+        while db.counts().len() < 8 {
+            match db.pop() {
+                None => db.flush(),
+                Some(p) => db.expand(&p),
+            }
+        }
+
+        assert!(db.counts().eq(expected.counts()));
+    }
+
+    #[test]
+    fn load_truncated() {
+        let db = run::<Naive>(Mode::OneSided, 4);
+        let mut checkpoint = vec![];
+        db.save(&mut checkpoint).unwrap();
+        checkpoint.pop();
+        assert!(Database::<Naive>::load(&mut checkpoint.as_slice()).is_err());
+    }
+
+    fn extend<T>(level: &[u8], limit: usize) -> Database<T>
+    where
+        T: Grid + BitOrAssign,
+    {
+        let mut db = Database::<T>::from_level(&mut &level[..]).unwrap();
+        while db.counts().len() < limit {
+            match db.pop() {
+                None => db.flush(),
+                Some(p) => db.expand(&p),
+            }
+        }
+        db
+    }
+
+    #[test]
+    fn extend_stored_level() {
+        let expected = run::<Naive>(Mode::Free, 8);
+
+        let mut level = vec![];
+        let mut db = run::<BlockGrid>(Mode::Free, 6);
+        db.save_level(&mut level).unwrap();
+
+        // Once processing has started, the level is not complete anymore
+        let p = db.pop().unwrap();
+        db.expand(&p);
+        assert!(db.save_level(&mut vec![]).is_err());
+
+        let db = extend::<Naive>(&level, 8);
+        assert_eq!(db.mode(), Mode::Free);
+        assert!(db.counts().eq(expected.counts()));
+        assert!(db.classes().eq(expected.classes()));
+        assert!(db.hole_free().eq(expected.hole_free()));
+        assert!(extend::<Board>(&level, 8).counts().eq(expected.counts()));
+        assert!(extend::<BlockGrid>(&level, 8)
+            .counts()
+            .eq(expected.counts()));
+    }
+
+    #[test]
+    fn files_of_another_lattice() {
+        let mut db = Database::<Naive, Hexagonal>::with_mode(Mode::Free);
+        while db.counts().len() < 4 {
+            db.expand_level(1);
+        }
+        let mut checkpoint = vec![];
+        db.save(&mut checkpoint).unwrap();
+        let mut level = vec![];
+        db.save_level(&mut level).unwrap();
+        let mut outbox = vec![vec![]];
+        db.route(&mut outbox).unwrap();
+
+        assert!(Database::<Naive>::load(&mut checkpoint.as_slice()).is_err());
+        assert!(Database::<Naive, Triangular>::from_level(&mut level.as_slice()).is_err());
+        assert!(Database::<Naive>::with_mode(Mode::Free)
+            .receive(&mut outbox[0].as_slice())
+            .is_err());
+
+        db.receive(&mut outbox[0].as_slice()).unwrap();
+        db.flush();
+        assert!(db.counts().eq(&[1, 1, 3, 7, 22]));
+        let db = Database::<Board, Hexagonal>::load(&mut checkpoint.as_slice()).unwrap();
+        assert!(db.counts().eq(&[1, 1, 3, 7]));
+        let db = Database::<BlockGrid, Hexagonal>::from_level(&mut level.as_slice()).unwrap();
+        assert_eq!(db.mode(), Mode::Free);
+    }
 }
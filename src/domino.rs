@@ -0,0 +1,149 @@
+//! Tilings of regions by dominoes. They are counted by a transfer over the cells of the
+//! region, column by column, keeping the set of cells of the next column already covered
+//! by the dominoes sticking out of the current one. The tilings themselves are enumerated
+//! as the solutions of a [`Puzzle`], for small regions.
+
+use std::collections::HashMap;
+
+use crate::{
+    board::Board,
+    grid::Grid,
+    polyomino::{Mode, Polyomino},
+    puzzle::Puzzle,
+    tiling::LabelledGrid,
+};
+
+/// The elements of the grid set to true, as columns of the bounding box, transposed
+/// if there are fewer columns than rows so that the columns are short
+fn columns<T: Grid>(region: &T) -> Vec<Vec<bool>> {
+    let (width, height) = region.get_bounding_box();
+    if width < height {
+        (0..height)
+            .map(|y| (0..width).map(|x| region.get(x, y)).collect())
+            .collect()
+    } else {
+        (0..width)
+            .map(|x| (0..height).map(|y| region.get(x, y)).collect())
+            .collect()
+    }
+}
+
+/// Count the tilings by dominoes of the elements of the grid set to true.
+/// Regions with different numbers of cells of each colour of a checkerboard have none
+pub fn count_tilings<T: Grid>(region: &T) -> u128 {
+    let columns = columns(region);
+    let height = columns.first().map_or(0, |column| column.len());
+    assert!(height <= 64, "The region is too large in both directions");
+
+    // Bit y of a profile is set if the cell of row y, in the current column up to the
+    // current row and in the next one after, is covered by a domino of a previous cell
+    let mut profiles: HashMap<u64, u128> = HashMap::from([(0, 1)]);
+    for (x, column) in columns.iter().enumerate() {
+        let next = columns.get(x + 1);
+        for y in 0..height {
+            let bit = 1 << y;
+            let mut after = HashMap::with_capacity(profiles.len());
+            let mut add = |profile, count| *after.entry(profile).or_insert(0) += count;
+            for (&profile, &count) in profiles.iter() {
+                if profile & bit != 0 {
+                    // Already covered, the bit now stands for the next column
+                    add(profile & !bit, count);
+                } else if column[y] {
+                    // A horizontal domino, or a vertical one
+                    if next.is_some_and(|next| next[y]) {
+                        add(profile | bit, count);
+                    }
+                    if y + 1 < height && column[y + 1] && profile & (bit << 1) == 0 {
+                        add(profile | bit << 1, count);
+                    }
+                } else {
+                    add(profile, count);
+                }
+            }
+            profiles = after;
+        }
+    }
+    profiles.get(&0).copied().unwrap_or(0)
+}
+
+/// Count the tilings of the polyomino by dominoes
+pub fn count_polyomino_tilings<T: Grid>(p: &Polyomino<T>) -> u128 {
+    count_tilings(&p.repr)
+}
+
+/// Enumerate the tilings by dominoes of the elements of the grid set to true, which
+/// must fit a board, labelled by domino over the bounding box of these elements.
+/// Their number grows exponentially with the area, so this is for small regions
+pub fn tilings<T: Grid>(region: &T) -> Vec<LabelledGrid> {
+    let domino = Polyomino::<Board>::from_cells(&[(0, 0), (1, 0)]);
+    let mut tilings = vec![];
+    Puzzle::tiling(&domino, region, Mode::OneSided).solve(|tiling| {
+        tilings.push(tiling.clone());
+        true
+    });
+    tilings
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        enumerator::Enumerator,
+        grids::{block_grid::BlockGrid, naive::Naive, tests::rectangle},
+        polyomino::Mode,
+    };
+
+    use super::*;
+
+    #[test]
+    fn rectangles() {
+        // Fibonacci numbers
+        let counts: Vec<_> = (1..=10).map(|n| count_tilings(&rectangle(2, n))).collect();
+        assert_eq!(counts, [1, 2, 3, 5, 8, 13, 21, 34, 55, 89]);
+        assert_eq!(count_tilings(&rectangle(3, 3)), 0);
+        assert_eq!(count_tilings(&rectangle(4, 4)), 36);
+        assert_eq!(count_tilings(&rectangle(8, 8)), 12988816);
+        assert_eq!(
+            count_tilings(&rectangle(4, 30)),
+            count_tilings(&rectangle(30, 4))
+        );
+        assert_eq!(tilings(&rectangle(4, 4)).len(), 36);
+    }
+
+    #[test]
+    fn aztec_diamonds() {
+        // The Aztec diamond of order n has 2^(n(n + 1) / 2) tilings
+        for n in 1..=8usize {
+            let mut region = Board::new();
+            for y in 0..2 * n {
+                let half = if y < n { y + 1 } else { 2 * n - y };
+                for x in n - half..n + half {
+                    region.set(x, y);
+                }
+            }
+            assert_eq!(count_tilings(&region), 1 << (n * (n + 1) / 2));
+        }
+    }
+
+    #[test]
+    fn same_counts_as_enumeration() {
+        let mut tileable = 0;
+        for p in Enumerator::<BlockGrid>::range(1..=8).mode(Mode::Free) {
+            let count = count_polyomino_tilings(&p);
+            assert_eq!(count, tilings(&p.repr).len() as u128);
+            if count > 0 {
+                tileable += 1;
+            }
+        }
+        assert!(tileable > 0);
+
+        // The checkerboard colouring forbids the tilings of the 2×2 square without
+        // two opposite corners, and of the T tetromino
+        let mut region = rectangle(2, 2);
+        region.unset(0, 0);
+        region.unset(1, 1);
+        assert_eq!(count_tilings(&region), 0);
+        let t = Polyomino::<Naive>::from_cells(&[(0, 0), (1, 0), (2, 0), (1, 1)]);
+        assert_eq!(count_polyomino_tilings(&t), 0);
+        assert!(tilings(&t.repr).is_empty());
+    }
+}
@@ -0,0 +1,149 @@
+//! Binary encoding shared by the on-disk formats.
+//! Integers are stored in little endian. Grids are stored independently
+//! of their backend, as the bits of their bounding box, column by column,
+//! packed from the least significant bit of each byte.
+
+use std::io::{self, Read, Write};
+
+use crate::grid::Grid;
+
+pub fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_owned())
+}
+
+pub fn write_u8<W: Write>(w: &mut W, v: u8) -> io::Result<()> {
+    w.write_all(&[v])
+}
+
+pub fn write_u32<W: Write>(w: &mut W, v: u32) -> io::Result<()> {
+    w.write_all(&v.to_le_bytes())
+}
+
+pub fn write_u64<W: Write>(w: &mut W, v: u64) -> io::Result<()> {
+    w.write_all(&v.to_le_bytes())
+}
+
+pub fn write_u128<W: Write>(w: &mut W, v: u128) -> io::Result<()> {
+    w.write_all(&v.to_le_bytes())
+}
+
+pub fn read_u8<R: Read>(r: &mut R) -> io::Result<u8> {
+    let mut buf = [0; 1];
+    r.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+pub fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+pub fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+pub fn read_u128<R: Read>(r: &mut R) -> io::Result<u128> {
+    let mut buf = [0; 16];
+    r.read_exact(&mut buf)?;
+    Ok(u128::from_le_bytes(buf))
+}
+
+/// Check that the next bytes are the expected magic number, followed
+/// by a version byte equal to the expected one
+pub fn read_header<R: Read>(r: &mut R, magic: &[u8], version: u8) -> io::Result<()> {
+    let mut buf = vec![0; magic.len()];
+    r.read_exact(&mut buf)?;
+    if buf != magic {
+        return Err(invalid_data("Unknown file format"));
+    }
+    let found = read_u8(r)?;
+    if found != version {
+        return Err(invalid_data(&format!(
+            "Unsupported version {found}, expected {version}"
+        )));
+    }
+    Ok(())
+}
+
+pub fn write_header<W: Write>(w: &mut W, magic: &[u8], version: u8) -> io::Result<()> {
+    w.write_all(magic)?;
+    write_u8(w, version)
+}
+
+/// Write the elements of the grid inside the bounding box of dimension dim
+pub fn write_grid<T, W>(w: &mut W, grid: &T, dim: (u8, u8)) -> io::Result<()>
+where
+    T: Grid,
+    W: Write,
+{
+    let mut bytes = vec![0u8; (dim.0 as usize * dim.1 as usize).div_ceil(8)];
+    let mut i = 0;
+    for x in 0..dim.0 as usize {
+        for y in 0..dim.1 as usize {
+            if grid.get(x, y) {
+                bytes[i / 8] |= 1 << (i % 8);
+            }
+            i += 1;
+        }
+    }
+    w.write_all(&bytes)
+}
+
+/// Read a grid written by [`write_grid`] with the same dimension.
+/// The space of the bounding box is reserved in the grid
+pub fn read_grid<T, R>(r: &mut R, dim: (u8, u8)) -> io::Result<T>
+where
+    T: Grid,
+    R: Read,
+{
+    let mut bytes = vec![0u8; (dim.0 as usize * dim.1 as usize).div_ceil(8)];
+    r.read_exact(&mut bytes)?;
+
+    let mut grid = T::new();
+    grid.reserve_space(dim.0 as usize, dim.1 as usize);
+    let mut i = 0;
+    for x in 0..dim.0 as usize {
+        for y in 0..dim.1 as usize {
+            if bytes[i / 8] & (1 << (i % 8)) != 0 {
+                grid.set(x, y);
+            }
+            i += 1;
+        }
+    }
+    Ok(grid)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grids::{block_grid::BlockGrid, naive::Naive};
+
+    use super::*;
+
+    #[test]
+    fn grid_round_trip() {
+        let mut grid = BlockGrid::new();
+        grid.reserve_space(5, 3);
+        grid.set(0, 0);
+        grid.set(4, 2);
+        grid.set(2, 1);
+
+        let mut buf = vec![];
+        write_grid(&mut buf, &grid, (5, 3)).unwrap();
+        assert_eq!(buf.len(), 2);
+
+        let naive: Naive = read_grid(&mut buf.as_slice(), (5, 3)).unwrap();
+        crate::grid::are_equal(&grid, &naive);
+    }
+
+    #[test]
+    fn bad_header() {
+        let mut buf = vec![];
+        write_header(&mut buf, b"ABCD", 2).unwrap();
+        assert!(read_header(&mut buf.as_slice(), b"ABCD", 2).is_ok());
+        assert!(read_header(&mut buf.as_slice(), b"ABCE", 2).is_err());
+        assert!(read_header(&mut buf.as_slice(), b"ABCD", 1).is_err());
+    }
+}
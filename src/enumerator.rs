@@ -0,0 +1,178 @@
+//! Iterate over the representatives of polyominoes, level by level, without
+//! driving the [`Database`] by hand.
+
+use std::ops::{BitOrAssign, RangeInclusive};
+
+use crate::{
+    database::Database,
+    grid::Grid,
+    polyomino::{Mode, Polyomino, Window},
+};
+
+/// Yield the representatives of all polyominoes whose number of squares is in a range,
+/// by increasing number of squares. Each polyomino is yielded as soon as it is expanded,
+/// so only the level in progress and the next one are stored.
+///
+/// ```
+/// use polyominos::{enumerator::Enumerator, grids::block_grid::BlockGrid};
+///
+/// assert_eq!(Enumerator::<BlockGrid>::new(5).count(), 18);
+/// ```
+pub struct Enumerator<'a, T>
+where
+    T: Grid + BitOrAssign,
+{
+    db: Database<T>,
+    sizes: RangeInclusive<usize>,
+    on_level: Option<Box<dyn FnMut(usize, u128) + 'a>>,
+    /// Number of levels given to `on_level`
+    reported: usize,
+}
+
+impl<'a, T> Enumerator<'a, T>
+where
+    T: Grid + BitOrAssign,
+{
+    /// Enumerate the one-sided polyominoes with `squares` squares
+    pub fn new(squares: usize) -> Self {
+        Self::range(squares..=squares)
+    }
+
+    /// Enumerate the one-sided polyominoes with a number of squares in `sizes`
+    pub fn range(sizes: RangeInclusive<usize>) -> Self {
+        assert!(
+            *sizes.start() > 0,
+            "There are no polyominoes with zero square"
+        );
+
+        Self {
+            db: Database::new(),
+            sizes,
+            on_level: None,
+            reported: 0,
+        }
+    }
+
+    /// Change which polyominoes are identified
+    pub fn mode(mut self, mode: Mode) -> Self {
+        assert!(self.reported == 0, "The enumeration has already started");
+        self.db = match self.db.window() {
+            Some(window) => Database::bounded(mode, window),
+            None => Database::with_mode(mode),
+        };
+        self
+    }
+
+    /// Only enumerate the polyominoes fitting the window, see [`Database::bounded`]
+    pub fn window(mut self, window: Window) -> Self {
+        assert!(self.reported == 0, "The enumeration has already started");
+        self.db = Database::bounded(self.db.mode(), window);
+        self
+    }
+
+    /// Call `f` with the number of squares and the number of polyominoes of each level,
+    /// once all its polyominoes are known, including the levels before the range
+    pub fn on_level<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(usize, u128) + 'a,
+    {
+        self.on_level = Some(Box::new(f));
+        self
+    }
+
+    /// The database of the enumeration, with the counts of the completed levels
+    pub fn database(&self) -> &Database<T> {
+        &self.db
+    }
+
+    fn report_levels(&mut self) {
+        let counts: Vec<_> = self.db.counts().copied().collect();
+        if let Some(f) = self.on_level.as_mut() {
+            for (i, &count) in counts.iter().enumerate().skip(self.reported) {
+                f(i + 1, count);
+            }
+        }
+        self.reported = counts.len();
+    }
+}
+
+impl<T> Iterator for Enumerator<'_, T>
+where
+    T: Grid + BitOrAssign,
+{
+    type Item = Polyomino<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let max = *self.sizes.end();
+        loop {
+            self.report_levels();
+
+            let Some(p) = self.db.pop() else {
+                // The queue holds the level of the number of counts
+                if self.db.counts().len() >= max {
+                    return None;
+                }
+                self.db.flush();
+                continue;
+            };
+
+            let squares = p.square_count as usize;
+            if squares < max {
+                self.db.expand(&p);
+            }
+            if self.sizes.contains(&squares) {
+                return Some(p);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use crate::{board::Board, grids::block_grid::BlockGrid};
+
+    use super::*;
+
+    #[test]
+    fn single_size() {
+        let mut count = 0;
+        for p in Enumerator::<BlockGrid>::new(8) {
+            assert_eq!(p.square_count, 8);
+            count += 1;
+        }
+        assert_eq!(count, 704);
+    }
+
+    #[test]
+    fn range_and_levels() {
+        let levels = RefCell::new(vec![]);
+        let enumerator = Enumerator::<Board>::range(3..=6)
+            .mode(Mode::Free)
+            .on_level(|squares, count| levels.borrow_mut().push((squares, count)));
+
+        let mut by_size = [0; 7];
+        for p in enumerator {
+            by_size[p.square_count as usize] += 1;
+        }
+        assert_eq!(by_size, [0, 0, 0, 2, 5, 12, 35]);
+        assert_eq!(
+            levels.into_inner(),
+            [(1, 1), (2, 1), (3, 2), (4, 5), (5, 12), (6, 35)]
+        );
+    }
+
+    #[test]
+    fn window() {
+        // The pentominoes of a 3×3 box: all but I, L, N and Y
+        let window = Window {
+            width: 3,
+            height: 3,
+        };
+        let enumerator = Enumerator::<BlockGrid>::new(5)
+            .window(window)
+            .mode(Mode::Free);
+        assert_eq!(enumerator.count(), 8);
+    }
+}
@@ -1,6 +1,6 @@
-use std::{fmt::Debug, hash::Hash};
+use std::{cmp::Ordering, fmt::Debug, hash::Hash};
 
-use crate::rotation::Rotation;
+use crate::rotation::{Rotation, Symmetry};
 
 /// A representation of the ℕxℕ grid, which maps coordinates to a boolean
 /// There are a finite number of elements set to true, thus there exists
@@ -18,6 +18,9 @@ use crate::rotation::Rotation;
 /// └───┴→   └──────┴→
 /// ```
 pub trait Grid: Clone + Debug + Ord + Hash {
+    /// The coordinates accepted by [`Grid::reserve_space`] are below this
+    const SIZE: usize;
+
     /// Create a new grid with all elements set to false
     fn new() -> Self;
 
@@ -46,6 +49,36 @@ pub trait Grid: Clone + Debug + Ord + Hash {
 
     /// Rotate the portion of the grid of dimension dim
     fn rotate(&self, dim: (u8, u8), r: Rotation) -> Self;
+
+    /// Mirror the portion of the grid of dimension dim along the X axis,
+    /// such that the element (x, y) is mapped to (dim.0 - 1 - x, y)
+    fn mirror(&self, dim: (u8, u8)) -> Self;
+
+    /// Apply a symmetry of the square to the portion of the grid of dimension dim.
+    /// The dimension of the result is given by [`Symmetry::apply_dim`]
+    fn transform(&self, dim: (u8, u8), s: Symmetry) -> Self {
+        if s.mirror {
+            self.mirror(dim).rotate(dim, s.rotation)
+        } else {
+            self.rotate(dim, s.rotation)
+        }
+    }
+
+    /// Compare the portions of dimension dim of the grids, as strings of elements row by
+    /// row from the bottom, false first. Unlike [`Ord`], which is free to follow the
+    /// layout of the backend, this order is the same for all backends
+    fn cmp_rows(&self, other: &Self, dim: (u8, u8)) -> Ordering {
+        for y in 0..dim.1 as usize {
+            for x in 0..dim.0 as usize {
+                match (self.get(x, y), other.get(x, y)) {
+                    (false, true) => return Ordering::Less,
+                    (true, false) => return Ordering::Greater,
+                    _ => (),
+                }
+            }
+        }
+        Ordering::Equal
+    }
 }
 
 pub fn transfer<T, U>(from: &T) -> U
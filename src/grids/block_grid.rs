@@ -7,7 +7,7 @@ use crate::grid::Grid;
 use crate::rotation::Rotation;
 
 #[repr(transparent)]
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Debug)]
 struct Block(u16);
 
 impl Block {
@@ -51,6 +51,13 @@ impl Block {
         self.0 == 0
     }
 
+    /// The elements (x, y) of the block with x < width and y < height
+    fn inside(width: usize, height: usize) -> u16 {
+        let columns = (1u32 << (4 * width.min(4))) - 1;
+        let rows = 0x1111 * ((1u16 << height.min(4)) - 1);
+        columns as u16 & rows
+    }
+
     fn count(&self) -> u32 {
         self.0.count_ones()
     }
@@ -160,88 +167,91 @@ impl Block {
         }
     }
 
-    fn batch_2_rotate_90_avx2(chunk: [u16; 2]) -> (u16, u16) {
+    /// Rotate two blocks at once. The caller must check that AVX2 is available
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2")]
+    unsafe fn batch_2_rotate_90_avx2(x1: u16, x2: u16) -> (u16, u16) {
         use std::arch::x86_64::*;
 
-        unsafe {
-            let mm_high = _mm_set1_epi16(x1 as i16);
-            let mm_low = _mm_set1_epi16(x2 as i16);
+        let mm_high = _mm_set1_epi16(x1 as i16);
+        let mm_low = _mm_set1_epi16(x2 as i16);
 
-            let mm = _mm256_set_m128i(mm_high, mm_low);
+        let mm = _mm256_set_m128i(mm_high, mm_low);
 
-            #[allow(overflowing_literals)]
-            let mask_part = _mm_set_epi64x(0x8080404020201010, 0x0808040402020101);
-            let mask = _mm256_set_m128i(mask_part, mask_part);
+        #[allow(overflowing_literals)]
+        let mask_part = _mm_set_epi64x(0x8080404020201010, 0x0808040402020101);
+        let mask = _mm256_set_m128i(mask_part, mask_part);
 
-            let bits = _mm256_and_si256(mm, mask);
-            let cmp = _mm256_cmpeq_epi8(bits, mask);
+        let bits = _mm256_and_si256(mm, mask);
+        let cmp = _mm256_cmpeq_epi8(bits, mask);
 
-            let shuffler_part = _mm_set_epi8(9, 1, 8, 0, 11, 3, 10, 2, 13, 5, 12, 4, 15, 7, 14, 6);
-            let shuffler = _mm256_set_m128i(shuffler_part, shuffler_part);
+        let shuffler_part = _mm_set_epi8(9, 1, 8, 0, 11, 3, 10, 2, 13, 5, 12, 4, 15, 7, 14, 6);
+        let shuffler = _mm256_set_m128i(shuffler_part, shuffler_part);
 
-            let shuffled = _mm256_shuffle_epi8(cmp, shuffler);
+        let shuffled = _mm256_shuffle_epi8(cmp, shuffler);
 
-            let result = _mm256_movemask_epi8(shuffled) as u32;
-            let result_high = (result >> 16) as u16;
-            let result_low = (result & 0xFFFF) as u16;
-            (result_high, result_low)
-        }
+        let result = _mm256_movemask_epi8(shuffled) as u32;
+        let result_high = (result >> 16) as u16;
+        let result_low = (result & 0xFFFF) as u16;
+        (result_high, result_low)
     }
 
-    fn batch_2_rotate_180_avx2(x1: u16, x2: u16) -> (u16, u16) {
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2")]
+    #[allow(dead_code)]
+    unsafe fn batch_2_rotate_180_avx2(x1: u16, x2: u16) -> (u16, u16) {
         use std::arch::x86_64::*;
 
-        unsafe {
-            let mm_high = _mm_set1_epi16(x1 as i16);
-            let mm_low = _mm_set1_epi16(x2 as i16);
+        let mm_high = _mm_set1_epi16(x1 as i16);
+        let mm_low = _mm_set1_epi16(x2 as i16);
 
-            let mm = _mm256_set_m128i(mm_high, mm_low);
+        let mm = _mm256_set_m128i(mm_high, mm_low);
 
-            #[allow(overflowing_literals)]
-            let mask_part = _mm_set_epi64x(0x8080404020201010, 0x0808040402020101);
-            let mask = _mm256_set_m128i(mask_part, mask_part);
+        #[allow(overflowing_literals)]
+        let mask_part = _mm_set_epi64x(0x8080404020201010, 0x0808040402020101);
+        let mask = _mm256_set_m128i(mask_part, mask_part);
 
-            let bits = _mm256_and_si256(mm, mask);
-            let cmp = _mm256_cmpeq_epi8(bits, mask);
+        let bits = _mm256_and_si256(mm, mask);
+        let cmp = _mm256_cmpeq_epi8(bits, mask);
 
-            let shuffler_part = _mm_set_epi8(0, 2, 4, 6, 8, 10, 12, 14, 1, 3, 5, 7, 9, 11, 13, 15);
-            let shuffler = _mm256_set_m128i(shuffler_part, shuffler_part);
+        let shuffler_part = _mm_set_epi8(0, 2, 4, 6, 8, 10, 12, 14, 1, 3, 5, 7, 9, 11, 13, 15);
+        let shuffler = _mm256_set_m128i(shuffler_part, shuffler_part);
 
-            let shuffled = _mm256_shuffle_epi8(cmp, shuffler);
+        let shuffled = _mm256_shuffle_epi8(cmp, shuffler);
 
-            let result = _mm256_movemask_epi8(shuffled) as u32;
-            let result_high = (result >> 16) as u16;
-            let result_low = (result & 0xFFFF) as u16;
-            (result_high, result_low)
-        }
+        let result = _mm256_movemask_epi8(shuffled) as u32;
+        let result_high = (result >> 16) as u16;
+        let result_low = (result & 0xFFFF) as u16;
+        (result_high, result_low)
     }
 
-    fn batch_2_rotate_270_avx2(x1: u16, x2: u16) -> (u16, u16) {
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2")]
+    #[allow(dead_code)]
+    unsafe fn batch_2_rotate_270_avx2(x1: u16, x2: u16) -> (u16, u16) {
         use std::arch::x86_64::*;
 
-        unsafe {
-            let mm_high = _mm_set1_epi16(x1 as i16);
-            let mm_low = _mm_set1_epi16(x2 as i16);
+        let mm_high = _mm_set1_epi16(x1 as i16);
+        let mm_low = _mm_set1_epi16(x2 as i16);
 
-            let mm = _mm256_set_m128i(mm_high, mm_low);
+        let mm = _mm256_set_m128i(mm_high, mm_low);
 
-            #[allow(overflowing_literals)]
-            let mask_part = _mm_set_epi64x(0x8080404020201010, 0x0808040402020101);
-            let mask = _mm256_set_m128i(mask_part, mask_part);
+        #[allow(overflowing_literals)]
+        let mask_part = _mm_set_epi64x(0x8080404020201010, 0x0808040402020101);
+        let mask = _mm256_set_m128i(mask_part, mask_part);
 
-            let bits = _mm256_and_si256(mm, mask);
-            let cmp = _mm256_cmpeq_epi8(bits, mask);
+        let bits = _mm256_and_si256(mm, mask);
+        let cmp = _mm256_cmpeq_epi8(bits, mask);
 
-            let shuffler_part = _mm_set_epi8(6, 14, 7, 15, 4, 12, 5, 13, 2, 10, 3, 11, 0, 8, 1, 9);
-            let shuffler = _mm256_set_m128i(shuffler_part, shuffler_part);
+        let shuffler_part = _mm_set_epi8(6, 14, 7, 15, 4, 12, 5, 13, 2, 10, 3, 11, 0, 8, 1, 9);
+        let shuffler = _mm256_set_m128i(shuffler_part, shuffler_part);
 
-            let shuffled = _mm256_shuffle_epi8(cmp, shuffler);
+        let shuffled = _mm256_shuffle_epi8(cmp, shuffler);
 
-            let result = _mm256_movemask_epi8(shuffled) as u32;
-            let result_high = (result >> 16) as u16;
-            let result_low = (result & 0xFFFF) as u16;
-            (result_high, result_low)
-        }
+        let result = _mm256_movemask_epi8(shuffled) as u32;
+        let result_high = (result >> 16) as u16;
+        let result_low = (result & 0xFFFF) as u16;
+        (result_high, result_low)
     }
 
     // fn batch_2_rotate_90_avx512(x1: u16, x2: u16, x3: u16, x4: u16) -> (u16, u16) {
@@ -339,11 +349,49 @@ impl Block {
             Rotation::R270 => Self(Self::rotate_270(self.0)),
         }
     }
+
+    fn mirror(&self) -> Self {
+        // Mirroring along the X axis reverses the order of the columns,
+        // which are the four nibbles of the block
+        let x = self.0;
+        Self((x << 12) | ((x & 0x00F0) << 4) | ((x & 0x0F00) >> 4) | (x >> 12))
+    }
 }
 
-impl Default for Block {
-    fn default() -> Self {
-        Self(0)
+/// Rotate all blocks by 90 degrees, using AVX2 if available
+fn rotate_blocks_90(blocks: &mut [Block]) {
+    #[cfg(target_arch = "x86_64")]
+    if crate::cpu::has_avx2() {
+        // SAFETY: AVX2 support was checked at runtime
+        unsafe { rotate_blocks_90_avx2(blocks) };
+        return;
+    }
+
+    rotate_blocks_90_scalar(blocks)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn rotate_blocks_90_avx2(blocks: &mut [Block]) {
+    // TODO: replace with ArrayChunk once released from nightly
+    let mut iter = blocks.chunks_exact_mut(2);
+    for chunk in iter.by_ref() {
+        // SAFETY: chunks have exactly two blocks
+        unsafe {
+            let (Block(x1), Block(x2)) = (chunk.get_unchecked(0), chunk.get_unchecked(1));
+            let (x1, x2) = Block::batch_2_rotate_90_avx2(*x1, *x2);
+            *chunk.get_unchecked_mut(0) = Block(x1);
+            *chunk.get_unchecked_mut(1) = Block(x2);
+        }
+    }
+    for block in iter.into_remainder().iter_mut() {
+        *block = block.rotate(Rotation::R90);
+    }
+}
+
+fn rotate_blocks_90_scalar(blocks: &mut [Block]) {
+    for block in blocks.iter_mut() {
+        *block = block.rotate(Rotation::R90);
     }
 }
 
@@ -428,6 +476,9 @@ impl Debug for BlockGrid {
 }
 
 impl Grid for BlockGrid {
+    // The metagrid grows on demand
+    const SIZE: usize = usize::MAX;
+
     fn new() -> Self {
         // By default, a meta grid of 1x1, so a grid of 4x4
         Self {
@@ -457,8 +508,8 @@ impl Grid for BlockGrid {
     }
 
     fn reserve_space(&mut self, x: usize, y: usize) {
-        let columns_wanted = x / 4 + if x % 4 == 0 { 0 } else { 1 };
-        let rows_wanted = y / 4 + if y % 4 == 0 { 0 } else { 1 };
+        let columns_wanted = x.div_ceil(4);
+        let rows_wanted = y.div_ceil(4);
 
         // Four cases:
         // - no need to extend
@@ -470,8 +521,9 @@ impl Grid for BlockGrid {
         if self.dim.0 >= columns_wanted && self.dim.1 >= rows_wanted {
             return;
         }
+
         // Case 1: we only need to add columns. Easy case
-        else if self.dim.0 < columns_wanted && self.dim.1 >= rows_wanted {
+        if self.dim.0 < columns_wanted && self.dim.1 >= rows_wanted {
             self.grid
                 .resize(columns_wanted * self.dim.1, Block::default());
             self.dim.0 = columns_wanted;
@@ -596,29 +648,8 @@ impl Grid for BlockGrid {
                 let mut block_grid = BlockGrid::new();
                 block_grid.reserve_space(dim.1 as usize, dim.0 as usize);
 
-                let (x, y) = dim;
-                let columns_wanted = x / 4 + if x % 4 == 0 { 0 } else { 1 };
-                let rows_wanted = y / 4 + if y % 4 == 0 { 0 } else { 1 };
-                let mut tmp = vec![Block::default(); (columns_wanted * rows_wanted) as usize];
-                tmp.copy_from_slice(block_grid.grid.as_slice());
-
-                // TODO: replace with ArrayChunk once released from nightly
-                let mut iter = tmp.as_mut_slice().chunks_exact_mut(2);
-                while let Some(chunk) = iter.next() {
-                    // let (x1, x2) = Block::batch_2_rotate_90_avx2(chunk[0].0, chunk[0].0);
-                    // chunk[0] = Block(x1);
-                    // chunk[1] = Block(x2);
-                    unsafe {
-                        let (Block(x1), Block(x2)) =
-                            (chunk.get_unchecked(0), chunk.get_unchecked(1));
-                        let (x1, x2) = Block::batch_2_rotate_90_avx2(*x1, *x2);
-                        *chunk.get_unchecked_mut(0) = Block(x1);
-                        *chunk.get_unchecked_mut(1) = Block(x2);
-                    }
-                }
-                for block in iter.into_remainder().into_iter() {
-                    *block = block.rotate(Rotation::R90);
-                }
+                let mut tmp = self.grid.clone();
+                rotate_blocks_90(&mut tmp);
 
                 for x in 0..self.dim.0 {
                     for y in 0..self.dim.1 {
@@ -660,6 +691,58 @@ impl Grid for BlockGrid {
             }
         }
     }
+
+    fn cmp_rows(&self, other: &Self, dim: (u8, u8)) -> Ordering {
+        let (width, height) = (dim.0 as usize, dim.1 as usize);
+        let block = |grid: &Self, x: usize, y: usize| {
+            if x < grid.dim.0 && y < grid.dim.1 {
+                grid.get_block(x, y).0
+            } else {
+                0
+            }
+        };
+
+        for y in 0..height.div_ceil(4) {
+            // The lowest row of the blocks with a difference, and the leftmost
+            // element of this row with a difference, with its value in self
+            let mut first: Option<(u32, bool)> = None;
+            for x in 0..width.div_ceil(4) {
+                let left = block(self, x, y);
+                let diff =
+                    (left ^ block(other, x, y)) & Block::inside(width - 4 * x, height - 4 * y);
+                if diff == 0 {
+                    continue;
+                }
+                let row = ((diff | diff >> 4 | diff >> 8 | diff >> 12) & 0xF).trailing_zeros();
+                if first.is_none_or(|(first_row, _)| row < first_row) {
+                    let bit = ((diff >> row) & 0x1111).trailing_zeros() + row;
+                    first = Some((row, left & (1 << bit) != 0));
+                }
+            }
+            match first {
+                Some((_, true)) => return Ordering::Greater,
+                Some((_, false)) => return Ordering::Less,
+                None => (),
+            }
+        }
+        Ordering::Equal
+    }
+
+    fn mirror(&self, dim: (u8, u8)) -> Self {
+        // Same constraint as for rotations
+        assert!((self.dim.0 * 4 - dim.0 as usize) < 4);
+
+        let mut block_grid = self.clone();
+        for x in 0..self.dim.0 {
+            for y in 0..self.dim.1 {
+                *block_grid.get_block_mut(self.dim.0 - 1 - x, y) = self.get_block(x, y).mirror();
+            }
+        }
+
+        block_grid.shift_x(dim.0 as isize - self.dim.0 as isize * 4);
+
+        block_grid
+    }
 }
 
 impl PartialEq for BlockGrid {
@@ -722,9 +805,8 @@ mod tests {
     #[test]
     fn block_get() {
         for i in 0..16 {
-            assert_eq!(
+            assert!(
                 Block(1 << i).get(i / 4, i % 4),
-                true,
                 "testing block 0b{:0>16b} with i={i}, x={}, y={}",
                 1 << i,
                 i / 4,
@@ -818,6 +900,83 @@ mod tests {
         assert_eq!(Block(0x5160).rotate(Rotation::R270), Block(0x0543));
     }
 
+    #[test]
+    fn block_mirror() {
+        assert_eq!(Block(0x1234).mirror(), Block(0x4321));
+        assert_eq!(Block(0xA88E).mirror(), Block(0xE88A));
+        for i in 0..u16::MAX {
+            let b = Block(i);
+            assert_eq!(b.mirror().mirror(), b, "testing double mirror of block {i}");
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn block_batch_rotate_avx2_is_scalar() {
+        if !is_x86_feature_detected!("avx2") {
+            return;
+        }
+
+        for i in 0..u16::MAX {
+            let (b1, b2) = (Block(i), Block(i.wrapping_mul(7919)));
+            let batches = [
+                (
+                    Rotation::R90,
+                    Block::batch_2_rotate_90_avx2 as unsafe fn(u16, u16) -> _,
+                ),
+                (Rotation::R180, Block::batch_2_rotate_180_avx2),
+                (Rotation::R270, Block::batch_2_rotate_270_avx2),
+            ];
+            for (r, batch) in batches.into_iter() {
+                assert_eq!(
+                    unsafe { batch(b1.0, b2.0) },
+                    (b1.rotate(r).0, b2.rotate(r).0),
+                    "testing batch rotation {r:?} of blocks {b1:?} and {b2:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn block_grid_rotate_scalar() {
+        // An odd number of blocks leaves one out of the batches of two
+        let blocks: Vec<_> = (0..7u16).map(|i| Block(i.wrapping_mul(7919))).collect();
+        let mut scalar = blocks.clone();
+        rotate_blocks_90_scalar(&mut scalar);
+        for (block, rotated) in blocks.iter().zip(scalar.iter()) {
+            assert_eq!(block.rotate(Rotation::R90), *rotated);
+        }
+        let mut dispatched = blocks.clone();
+        rotate_blocks_90(&mut dispatched);
+        assert_eq!(dispatched, scalar);
+
+        let mut grid = BlockGrid::new();
+        grid.reserve_space(7, 5);
+        grid.set(0, 0);
+        grid.set(1, 0);
+        grid.set(6, 4);
+        let rotated = grid.rotate((7, 5), Rotation::R90);
+        assert!(rotated.get(4, 0));
+        assert!(rotated.get(4, 1));
+        assert!(rotated.get(0, 6));
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn block_grid_rotate_avx2_is_scalar() {
+        if !is_x86_feature_detected!("avx2") {
+            return;
+        }
+
+        for length in 0..9u16 {
+            let blocks: Vec<_> = (0..length).map(|i| Block(i.wrapping_mul(7919))).collect();
+            let (mut scalar, mut avx2) = (blocks.clone(), blocks);
+            rotate_blocks_90_scalar(&mut scalar);
+            unsafe { rotate_blocks_90_avx2(&mut avx2) };
+            assert_eq!(scalar, avx2);
+        }
+    }
+
     #[test]
     fn block_rotate_2x90_is_180() {
         for i in 0..u16::MAX {
@@ -2,4 +2,4 @@ pub mod block_grid;
 pub mod naive;
 
 #[cfg(test)]
-mod tests;
+pub(crate) mod tests;
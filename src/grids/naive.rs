@@ -12,6 +12,8 @@ const SIZE: usize = 32;
 pub struct Naive([[bool; SIZE]; SIZE]);
 
 impl Grid for Naive {
+    const SIZE: usize = SIZE;
+
     fn new() -> Self {
         Self([[false; SIZE]; SIZE])
     }
@@ -52,7 +54,7 @@ impl Grid for Naive {
 
         for x in 0..dim_x {
             for y in (0..SIZE).rev() {
-                if self.0[x][y] == true {
+                if self.0[x][y] {
                     dim_y = dim_y.max(y + 1);
                     break;
                 }
@@ -138,6 +140,19 @@ impl Grid for Naive {
 
         new
     }
+
+    fn mirror(&self, dim: (u8, u8)) -> Self {
+        let mut new = Self::new();
+        let dim = (dim.0 as usize, dim.1 as usize);
+
+        for x in 0..dim.0 {
+            for y in 0..dim.1 {
+                new.0[x][y] = self.0[dim.0 - 1 - x][y];
+            }
+        }
+
+        new
+    }
 }
 
 impl BitOrAssign for Naive {
@@ -188,4 +203,19 @@ mod tests {
         grid.unset(1, 2);
         assert_eq!(grid.get_bounding_box(), (3, 2));
     }
+
+    #[test]
+    fn test_naive_mirror() {
+        let mut grid = Naive::new();
+        grid.set(0, 0);
+        grid.set(1, 0);
+        grid.set(1, 2);
+
+        let mirrored = grid.mirror((3, 3));
+        assert_eq!(mirrored.count(), 3);
+        assert!(mirrored.get(2, 0));
+        assert!(mirrored.get(1, 0));
+        assert!(mirrored.get(1, 2));
+        assert_eq!(mirrored.mirror((3, 3)), grid);
+    }
 }
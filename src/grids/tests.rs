@@ -1,6 +1,10 @@
-use crate::grid::Grid;
+use crate::{
+    board::Board,
+    grid::{are_equal, transfer, Grid},
+    rotation::Symmetry,
+};
 
-use super::naive::Naive;
+use super::{block_grid::BlockGrid, naive::Naive};
 
 use paste::paste;
 macro_rules! test {
@@ -16,10 +20,76 @@ macro_rules! test {
 }
 
 test!(Naive);
+test!(BlockGrid);
+test!(Board);
+
+/// The region made of the rectangle of the given dimension at the origin
+pub(crate) fn rectangle(width: usize, height: usize) -> Naive {
+    let mut region = Naive::new();
+    for x in 0..width {
+        for y in 0..height {
+            region.set(x, y);
+        }
+    }
+    region
+}
+
+/// A shape given by its dimension and its elements
+type Shape = ((u8, u8), Vec<(usize, usize)>);
+
+/// Asymmetric shapes
+fn shapes() -> Vec<Shape> {
+    vec![
+        ((1, 1), vec![(0, 0)]),
+        ((3, 2), vec![(0, 0), (1, 0), (2, 0), (2, 1)]),
+        ((4, 5), vec![(1, 1), (1, 2), (1, 3), (2, 3)]),
+        ((7, 3), vec![(0, 0), (1, 0), (1, 1), (2, 1), (5, 1), (6, 2)]),
+        ((8, 8), vec![(0, 7), (3, 3), (3, 4), (4, 4), (7, 0)]),
+        ((9, 6), vec![(0, 0), (2, 5), (8, 1), (8, 2), (4, 3)]),
+        ((31, 2), vec![(0, 0), (30, 1), (15, 1)]),
+        ((3, 31), vec![(0, 0), (2, 30), (1, 17)]),
+    ]
+}
 
 fn test_grid<T>()
 where
     T: Grid,
 {
-    // Do stuff
+    for (dim, elements) in shapes().into_iter() {
+        let mut grid = T::new();
+        grid.reserve_space(dim.0 as usize, dim.1 as usize);
+        let mut witness = Naive::new();
+        for &(x, y) in elements.iter() {
+            grid.set(x, y);
+            witness.set(x, y);
+        }
+        assert_eq!(grid.count(), elements.len() as u32);
+        are_equal(&grid, &witness);
+
+        for s in Symmetry::ALL {
+            are_equal(&grid.transform(dim, s), &witness.transform(dim, s));
+        }
+        are_equal(&grid.mirror(dim).mirror(dim), &witness);
+
+        // Images of the same dimension are ordered like the ones of the witness
+        for s in Symmetry::ALL {
+            for t in Symmetry::ALL
+                .into_iter()
+                .filter(|t| t.apply_dim(dim) == s.apply_dim(dim))
+            {
+                let image = s.apply_dim(dim);
+                let (left, right) = (grid.transform(dim, s), grid.transform(dim, t));
+                assert_eq!(
+                    left.cmp_rows(&right, image),
+                    witness
+                        .transform(dim, s)
+                        .cmp_rows(&witness.transform(dim, t), image)
+                );
+            }
+        }
+        are_equal(
+            &transfer::<_, Naive>(&grid.mirror(dim)),
+            &witness.mirror(dim),
+        );
+    }
 }
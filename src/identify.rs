@@ -0,0 +1,267 @@
+//! Identification of shapes given by the user: a grid, a list of cells or rows of
+//! characters. Shapes are brought to their representative, and named after the
+//! conventional letters of the tetrominoes and pentominoes.
+
+use std::{collections::HashSet, sync::OnceLock};
+
+use crate::{
+    grid::Grid,
+    grids::naive::Naive,
+    polyomino::{canonical, smallest_rotation, Mode, Polyomino},
+    rotation::Symmetry,
+};
+
+/// The conventional names of the tetrominoes and pentominoes, with their shapes drawn
+/// like for [`parse_shape`]. The chiral pentominoes are drawn as their letter, and the
+/// name of their mirror image is primed
+const NAMES: [(&str, &str); 25] = [
+    ("I", "OOOO"),
+    ("O", "OO/OO"),
+    ("T", "OOO/.O."),
+    ("S", ".OO/OO."),
+    ("Z", "OO./.OO"),
+    ("J", ".O/.O/OO"),
+    ("L", "O./O./OO"),
+    ("F", ".OO/OO./.O."),
+    ("F'", "OO./.OO/.O."),
+    ("I", "OOOOO"),
+    ("L", "O./O./O./OO"),
+    ("L'", ".O/.O/.O/OO"),
+    ("N", ".O/OO/O./O."),
+    ("N'", "O./OO/.O/.O"),
+    ("P", "OO/OO/O."),
+    ("P'", "OO/OO/.O"),
+    ("T", "OOO/.O./.O."),
+    ("U", "O.O/OOO"),
+    ("V", "O../O../OOO"),
+    ("W", "O../OO./.OO"),
+    ("X", ".O./OOO/.O."),
+    ("Y", ".O/OO/.O/.O"),
+    ("Y'", "O./OO/O./O."),
+    ("Z", "OO./.O./.OO"),
+    ("Z'", ".OO/.O./OO."),
+];
+
+/// What is known of a shape
+#[derive(Clone, Debug)]
+pub struct Identification<T>
+where
+    T: Grid,
+{
+    pub square_count: usize,
+    /// The representative of the shape for the mode
+    pub canonical: Polyomino<T>,
+    /// The symmetry mapping the shape to its representative
+    pub symmetry: Symmetry,
+    /// The conventional name of the shape, up to rotations, if it has one
+    pub name: Option<&'static str>,
+}
+
+/// Parse rows of 'O' (or '#', 'X') and '.' (or ' ', '_') from top to bottom,
+/// separated by '/' or new lines, into the coordinates of the squares from the bottom
+pub fn parse_shape(shape: &str) -> Result<Vec<(usize, usize)>, String> {
+    let rows: Vec<_> = shape
+        .split(['/', '\n'])
+        .map(|row| row.trim())
+        .filter(|row| !row.is_empty())
+        .collect();
+    let mut cells = vec![];
+    for (j, row) in rows.iter().enumerate() {
+        for (x, c) in row.chars().enumerate() {
+            match c {
+                'O' | 'o' | '#' | 'X' | 'x' => cells.push((x, rows.len() - 1 - j)),
+                '.' | ' ' | '_' => (),
+                _ => return Err(format!("Unexpected character {c:?} in the shape")),
+            }
+        }
+    }
+    Ok(cells)
+}
+
+/// A name, with the cells of the smallest rotation of its shape
+type Named = (&'static str, Vec<(usize, usize)>);
+
+/// The named shapes, whose cells do not depend on the grid
+fn named_cells() -> &'static [Named] {
+    static NAMED: OnceLock<Vec<Named>> = OnceLock::new();
+    NAMED.get_or_init(|| {
+        NAMES
+            .iter()
+            .map(|&(name, shape)| {
+                let named = Polyomino::<Naive>::from_cells(&parse_shape(shape).unwrap());
+                (name, smallest_rotation(named).0.cells())
+            })
+            .collect()
+    })
+}
+
+/// Return the conventional name of the polyomino, if it has one
+pub fn name<T: Grid>(p: &Polyomino<T>) -> Option<&'static str> {
+    // Only the tetrominoes and pentominoes are named
+    if !(4..=5).contains(&p.square_count) {
+        return None;
+    }
+    let cells = smallest_rotation(p.clone()).0.cells();
+    named_cells()
+        .iter()
+        .find_map(|(name, named)| (*named == cells).then_some(*name))
+}
+
+/// Identify the polyomino, among the polyominoes identified by the mode
+pub fn identify<T: Grid>(p: Polyomino<T>, mode: Mode) -> Identification<T> {
+    let name = name(&p);
+    let square_count = p.square_count as usize;
+    let (canonical, symmetry) = canonical(p, mode);
+    Identification {
+        square_count,
+        canonical,
+        symmetry,
+        name,
+    }
+}
+
+/// Identify the shape made of the cells, which must be connected by their sides,
+/// and fit the grid with the margin around them
+pub fn identify_cells<T: Grid>(
+    cells: &[(usize, usize)],
+    mode: Mode,
+) -> Result<Identification<T>, String> {
+    let Some(&first) = cells.first() else {
+        return Err("The shape has no square".to_string());
+    };
+
+    // The dimension of the polyomino, with the margin, is below the size of the grid
+    let largest = (T::SIZE - 1).min(u8::MAX as usize) - 2;
+    let extent = |coordinate: fn(&(usize, usize)) -> usize| {
+        let max = cells.iter().map(coordinate).max().unwrap();
+        let min = cells.iter().map(coordinate).min().unwrap();
+        max - min + 1
+    };
+    if extent(|&(x, _)| x) > largest || extent(|&(_, y)| y) > largest {
+        return Err(format!(
+            "The shape is too large, at most {largest} squares across fit the grid"
+        ));
+    }
+
+    // Walk from the first cell through the neighbours
+    let squares: HashSet<_> = cells.iter().copied().collect();
+    let mut reached = HashSet::from([first]);
+    let mut stack = vec![first];
+    while let Some((x, y)) = stack.pop() {
+        let neighbours = [
+            (x.wrapping_sub(1), y),
+            (x + 1, y),
+            (x, y.wrapping_sub(1)),
+            (x, y + 1),
+        ];
+        for neighbour in neighbours {
+            if squares.contains(&neighbour) && reached.insert(neighbour) {
+                stack.push(neighbour);
+            }
+        }
+    }
+    if reached.len() != squares.len() {
+        return Err("The squares of the shape are not connected".to_string());
+    }
+    if squares.len() > u8::MAX as usize {
+        return Err(format!("The shape has more than {} squares", u8::MAX));
+    }
+
+    let cells: Vec<_> = squares.into_iter().collect();
+    Ok(identify(Polyomino::from_cells(&cells), mode))
+}
+
+/// Identify the shape made of the elements of the grid set to true
+pub fn identify_grid<T: Grid, U: Grid>(grid: &U, mode: Mode) -> Result<Identification<T>, String> {
+    let (width, height) = grid.get_bounding_box();
+    let cells: Vec<_> = (0..width)
+        .flat_map(|x| (0..height).map(move |y| (x, y)))
+        .filter(|&(x, y)| grid.get(x, y))
+        .collect();
+    identify_cells(&cells, mode)
+}
+
+/// Identify the shape drawn like for [`parse_shape`]
+pub fn identify_shape<T: Grid>(shape: &str, mode: Mode) -> Result<Identification<T>, String> {
+    identify_cells(&parse_shape(shape)?, mode)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        board::Board,
+        enumerator::Enumerator,
+        grids::{block_grid::BlockGrid, naive::Naive},
+        rotation::Rotation,
+    };
+
+    use super::*;
+
+    #[test]
+    fn names_of_one_sided_polyominoes() {
+        for (n, count) in [(3, 0), (4, 7), (5, 18), (6, 0)] {
+            let mut names: Vec<_> = Enumerator::<BlockGrid>::new(n)
+                .mode(Mode::OneSided)
+                .filter_map(|p| name(&p))
+                .collect();
+            names.sort();
+            names.dedup();
+            assert_eq!(names.len(), count);
+        }
+    }
+
+    #[test]
+    fn chirality() {
+        let l = identify_shape::<Naive>("O./O./OO", Mode::OneSided).unwrap();
+        assert_eq!(l.name, Some("L"));
+        let j = identify_shape::<Naive>("OO./O../O..", Mode::OneSided).unwrap();
+        assert_eq!(j.name, Some("J"));
+        assert_eq!(j.square_count, 4);
+
+        // The mirror images share their representative only among free polyominoes
+        assert_ne!(l.canonical.cells(), j.canonical.cells());
+        let l = identify_shape::<Naive>("O./O./OO", Mode::Free).unwrap();
+        let j = identify_shape::<Naive>("OO./O../O..", Mode::Free).unwrap();
+        assert_eq!(l.canonical.cells(), j.canonical.cells());
+        assert_ne!(l.symmetry.mirror, j.symmetry.mirror);
+
+        for (shape, expected) in [
+            ("OO./.OO/.O.", "F'"),
+            ("O./OO/O./O.", "Y'"),
+            ("...O/OOOO", "L"),
+            ("OOOO/...O", "L'"),
+            ("OOO/O.O", "U"),
+        ] {
+            let id = identify_shape::<BlockGrid>(shape, Mode::Free).unwrap();
+            assert_eq!(id.name, Some(expected), "{shape}");
+            assert_eq!(id.square_count, 5);
+        }
+    }
+
+    #[test]
+    fn symmetry_to_canonical() {
+        let mut grid = Board::new();
+        for (x, y) in [(5, 3), (6, 3), (7, 3), (7, 4)] {
+            grid.set(x, y);
+        }
+        let id = identify_grid::<Board, _>(&grid, Mode::OneSided).unwrap();
+        assert_eq!(id.name, Some("L"));
+        let p = Polyomino::<Board>::from_cells(&[(0, 0), (1, 0), (2, 0), (2, 1)]);
+        let turned = p.repr.rotate(p.dimension, id.symmetry.rotation);
+        assert_eq!(turned, id.canonical.repr);
+        assert_ne!(id.symmetry.rotation, Rotation::R0);
+
+        assert!(identify_shape::<Naive>("O.O", Mode::Free).is_err());
+        assert!(identify_shape::<Naive>("./.", Mode::Free).is_err());
+        assert!(identify_shape::<Naive>("O?", Mode::Free).is_err());
+        // The margin around the squares must fit the grid
+        assert!(identify_shape::<Naive>(&"O".repeat(29), Mode::Free).is_ok());
+        assert!(identify_shape::<Naive>(&"O".repeat(30), Mode::Free).is_err());
+        assert!(identify_shape::<Board>(&"O/".repeat(40), Mode::Free).is_err());
+        assert!(identify_shape::<BlockGrid>(&"O".repeat(40), Mode::Free).is_ok());
+        assert_eq!(
+            identify_shape::<Naive>("OOO/OOO", Mode::Free).unwrap().name,
+            None
+        );
+    }
+}
@@ -0,0 +1,723 @@
+//! Lattices of cells, with their neighbourhood and their group of rotations. Polyforms
+//! of all lattices are stored in the grids of [`Polyomino`], the cells of the square
+//! lattice being the elements of the grid, and the ones of the other lattices being
+//! mapped to them by a shear:
+//! - on the hexagonal lattice, the element (x, y) is the hexagon of axial coordinates
+//!   (x, y), whose neighbours are (x ± 1, y), (x, y ± 1), (x + 1, y - 1) and (x - 1, y + 1)
+//! - on the triangular lattice, the elements (2u, v) and (2u + 1, v) are the triangles
+//!   pointing up and down in the rhombus (u, v) of the sheared square lattice
+//!
+//! Only the square lattice has fast implementations of the operations on polyforms,
+//! the other ones going through the coordinates of the cells, with the same masks
+//! and anti-masks.
+
+use std::fmt::Debug;
+
+use crate::{
+    grid::Grid,
+    packed::Packed,
+    polyomino::{self, decline_within, Mode, Polyomino},
+    rotation::{Symmetry, SymmetryClass},
+};
+
+pub trait Lattice: Copy + Debug + Default + Send + Sync + 'static {
+    /// The symmetries of the lattice fixing its origin
+    type Symmetry: Copy + Debug + PartialEq + Send + Sync;
+
+    /// Identifier of the lattice in the files of the database
+    const ID: u8;
+    const NAME: &'static str;
+    /// Number of rotations, the symmetries being these rotations and as many reflections
+    const ROTATIONS: usize;
+    /// Translations mapping the lattice onto itself move by multiples of this in X
+    const PERIOD: isize = 1;
+
+    /// Return the cells sharing a side with the cell
+    fn neighbours(cell: (isize, isize)) -> Vec<(isize, isize)>;
+
+    /// Return the symmetries identifying the polyforms of the mode, the identity first
+    fn symmetries(mode: Mode) -> Vec<Self::Symmetry>;
+
+    /// Return the image of the cell by the symmetry, around the origin of the lattice
+    fn transform(cell: (isize, isize), s: Self::Symmetry) -> (isize, isize);
+
+    /// Return a bound on the area of the grids, with their margin, of the representatives
+    /// for the mode of the polyforms with `cells` cells
+    fn largest_grid(cells: usize, mode: Mode) -> usize;
+
+    /// Return the largest number of cells such that the representatives for the mode
+    /// fit in a [`Packed`] grid, which the database stores
+    fn max_cells(mode: Mode) -> usize {
+        (1..)
+            .take_while(|&cells| Self::largest_grid(cells, mode) <= Packed::MAX_AREA)
+            .last()
+            .unwrap_or(0)
+    }
+
+    /// Return the representatives of the polyforms with one cell
+    fn seeds<T: Grid>(mode: Mode) -> Vec<Polyomino<T>> {
+        let mut seeds: Vec<Polyomino<T>> = vec![];
+        for x in 0..Self::PERIOD {
+            let seed = build::<T, Self>(vec![(x, 0)], Self::neighbours((x, 0)));
+            let seed = Self::canonical(seed, mode).0;
+            if seeds.iter().all(|s| s.repr != seed.repr) {
+                seeds.push(seed);
+            }
+        }
+        seeds
+    }
+
+    /// Add the cell (x, y) of the mask to the polyform, like [`Polyomino::add_square`]:
+    /// its empty neighbours join the mask, except the ones of the anti-mask, which is
+    /// in the coordinates of the polyform before the addition
+    fn add_square<T: Grid>(p: &mut Polyomino<T>, x: usize, y: usize, anti_mask: &T) {
+        let cell = (x as isize, y as isize);
+        let mut repr = cells(&p.repr, p.dimension);
+        repr.push(cell);
+        let mut mask = cells(&p.mask, p.dimension);
+        mask.retain(|&c| c != cell);
+        for (x, y) in Self::neighbours(cell) {
+            let anti_mask = x >= 0 && y >= 0 && anti_mask.get(x as usize, y as usize);
+            if !repr.contains(&(x, y)) && !mask.contains(&(x, y)) && !anti_mask {
+                mask.push((x, y));
+            }
+        }
+        *p = build::<T, Self>(repr, mask);
+    }
+
+    /// Return the polyforms obtained by adding a cell of the mask, whose bounding box
+    /// without the margin `fits`, like [`decline_within`]: the cells of the mask are
+    /// added in the order of the coordinates, each one being in the anti-mask of the
+    /// polyforms with the next ones
+    fn decline_within<T, F>(p: &Polyomino<T>, fits: F) -> Vec<Polyomino<T>>
+    where
+        T: Grid,
+        F: Fn((usize, usize)) -> bool,
+    {
+        let mut children = vec![];
+        let mut mask = p.mask.clone();
+        let mut anti_mask = T::new();
+        anti_mask.reserve_space((p.dimension.0 + 1) as usize, (p.dimension.1 + 1) as usize);
+        for (x, y) in cells(&p.mask, p.dimension) {
+            let (x, y) = (x as usize, y as usize);
+            let mut child = Polyomino {
+                square_count: p.square_count,
+                dimension: p.dimension,
+                repr: p.repr.clone(),
+                mask: mask.clone(),
+            };
+            Self::add_square(&mut child, x, y, &anti_mask);
+            if fits(child.size()) {
+                children.push(child);
+            }
+            anti_mask.set(x, y);
+            mask.unset(x, y);
+        }
+        children
+    }
+
+    /// Return the image of the polyform by the symmetry, with its mask, placed
+    /// against the origin
+    fn apply<T: Grid>(p: &Polyomino<T>, s: Self::Symmetry) -> Polyomino<T> {
+        let image = |grid: &T| {
+            cells(grid, p.dimension)
+                .into_iter()
+                .map(|cell| Self::transform(cell, s))
+                .collect()
+        };
+        build::<T, Self>(image(&p.repr), image(&p.mask))
+    }
+
+    /// Return the representative of the polyform for the mode: the smallest image by
+    /// the symmetries of the mode, by dimension and then by the cells row by row,
+    /// with the symmetry mapping the polyform to it
+    fn canonical<T: Grid>(p: Polyomino<T>, mode: Mode) -> (Polyomino<T>, Self::Symmetry) {
+        let cells = cells(&p.repr, p.dimension);
+        let images = Self::symmetries(mode).into_iter().map(|s| {
+            let image = cells.iter().map(|&cell| Self::transform(cell, s));
+            let mut image = place::<Self>(image.collect());
+            image.sort_by_key(|&(x, y)| (y, x));
+            (image, s)
+        });
+        let (_, s) = images
+            .min_by_key(|(image, _)| (dimension(image), image.clone()))
+            .unwrap();
+        (Self::apply(&p, s), s)
+    }
+
+    /// Return the symmetry class of the polyform, if the lattice has classes
+    fn symmetry_class<T: Grid>(_p: &Polyomino<T>) -> Option<SymmetryClass> {
+        None
+    }
+
+    /// Return whether some empty cells are enclosed by the polyform
+    fn has_holes<T: Grid>(p: &Polyomino<T>) -> bool {
+        let (width, height) = (p.dimension.0 as isize, p.dimension.1 as isize);
+        let inside = |(x, y): (isize, isize)| 0 <= x && x < width && 0 <= y && y < height;
+        let empty = |(x, y): (isize, isize)| !p.repr.get(x as usize, y as usize);
+
+        // The cells of the border are outside of the polyform, and an empty cell
+        // leaving the bounding box goes through one of them
+        let mut outside = vec![false; (width * height) as usize];
+        let mut stack: Vec<_> = (0..width)
+            .flat_map(|x| (0..height).map(move |y| (x, y)))
+            .filter(|&(x, y)| x == 0 || y == 0 || x == width - 1 || y == height - 1)
+            .filter(|&cell| empty(cell))
+            .collect();
+        for &(x, y) in stack.iter() {
+            outside[(y * width + x) as usize] = true;
+        }
+        while let Some(cell) = stack.pop() {
+            for (x, y) in Self::neighbours(cell) {
+                if inside((x, y)) && empty((x, y)) && !outside[(y * width + x) as usize] {
+                    outside[(y * width + x) as usize] = true;
+                    stack.push((x, y));
+                }
+            }
+        }
+
+        let empty_cells = (width * height) as usize - p.square_count as usize;
+        outside.iter().filter(|&&o| o).count() != empty_cells
+    }
+}
+
+/// A symmetry of a lattice generated by a rotation and a reflection: the
+/// reflection if `mirror`, then `rotation` steps of the rotation
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Turn {
+    pub mirror: bool,
+    pub rotation: usize,
+}
+
+impl Turn {
+    /// Return the symmetries of the mode on a lattice with `rotations` rotations
+    fn all(rotations: usize, mode: Mode) -> Vec<Turn> {
+        let (mirrors, rotations) = match mode {
+            Mode::Fixed => ([false].as_slice(), 1),
+            Mode::OneSided => ([false].as_slice(), rotations),
+            Mode::Free => ([false, true].as_slice(), rotations),
+        };
+        mirrors
+            .iter()
+            .flat_map(|&mirror| (0..rotations).map(move |rotation| Turn { mirror, rotation }))
+            .collect()
+    }
+}
+
+/// The spans (a, b, c) of a polyform across three families of lines, which the rotations
+/// permute cyclically and the reflections swap. Each side between two cells is crossed
+/// by `crossed` of the families, so that the spans add up to at most `crossed` times the
+/// number of cells minus one, and a span is at most `slack` more than the two others
+fn spans(cells: usize, crossed: usize, slack: usize) -> Vec<(usize, usize, usize)> {
+    let sum = crossed * (cells - 1);
+    let mut spans = vec![];
+    for a in 0..=sum {
+        for b in 0..=sum - a {
+            for c in 0..=sum - a - b {
+                if a <= b + c + slack && b <= a + c + slack && c <= a + b + slack {
+                    spans.push((a, b, c));
+                }
+            }
+        }
+    }
+    spans
+}
+
+/// The spans across the families of lines along the X and Y axes of the grid in
+/// the images of a polyform by the symmetries of the mode, see [`spans`]
+fn orientations((a, b, c): (usize, usize, usize), mode: Mode) -> Vec<(usize, usize)> {
+    match mode {
+        Mode::Fixed => vec![(a, b)],
+        Mode::OneSided => vec![(a, b), (b, c), (c, a)],
+        Mode::Free => vec![(a, b), (b, c), (c, a), (b, a), (c, b), (a, c)],
+    }
+}
+
+/// The cells of the grid within the dimension, in the order of the coordinates
+fn cells<T: Grid>(grid: &T, dimension: (u8, u8)) -> Vec<(isize, isize)> {
+    let mut cells = vec![];
+    for x in 0..dimension.0 as usize {
+        for y in 0..dimension.1 as usize {
+            if grid.get(x, y) {
+                cells.push((x as isize, y as isize));
+            }
+        }
+    }
+    cells
+}
+
+/// The translation placing the cells against the origin with a margin of one element,
+/// or two in X when the translation would not map the lattice onto itself
+fn offset<L: Lattice>(cells: &[(isize, isize)]) -> (isize, isize) {
+    let min_x = cells.iter().map(|&(x, _)| x).min().unwrap();
+    let min_y = cells.iter().map(|&(_, y)| y).min().unwrap();
+    let dx = 1 - min_x;
+    let dx = dx + (L::PERIOD - dx.rem_euclid(L::PERIOD)) % L::PERIOD;
+    (dx, 1 - min_y)
+}
+
+/// Translate the cells against the origin, see [`offset`]
+fn place<L: Lattice>(mut cells: Vec<(isize, isize)>) -> Vec<(isize, isize)> {
+    let (dx, dy) = offset::<L>(&cells);
+    for (x, y) in cells.iter_mut() {
+        *x += dx;
+        *y += dy;
+    }
+    cells
+}
+
+/// The dimension of the grid holding the placed cells, with the margin
+fn dimension(cells: &[(isize, isize)]) -> (u8, u8) {
+    let max_x = cells.iter().map(|&(x, _)| x).max().unwrap();
+    let max_y = cells.iter().map(|&(_, y)| y).max().unwrap();
+    ((max_x + 2) as u8, (max_y + 2) as u8)
+}
+
+/// Build the polyform made of the cells, with the mask, both translated so that
+/// the cells are placed against the origin. The mask is made of neighbours of the
+/// cells, which stay in the margin
+fn build<T: Grid, L: Lattice>(
+    cells: Vec<(isize, isize)>,
+    mask: Vec<(isize, isize)>,
+) -> Polyomino<T> {
+    let (dx, dy) = offset::<L>(&cells);
+    let cells = place::<L>(cells);
+    let dimension = dimension(&cells);
+
+    let grid = |cells: &[(isize, isize)], (dx, dy): (isize, isize)| {
+        let mut grid = T::new();
+        grid.reserve_space(dimension.0 as usize, dimension.1 as usize);
+        for &(x, y) in cells.iter() {
+            grid.set((x + dx) as usize, (y + dy) as usize);
+        }
+        grid
+    };
+    Polyomino::from(dimension, grid(&cells, (0, 0)), grid(&mask, (dx, dy)))
+}
+
+/// The square lattice of polyominoes, with four neighbours and four rotations
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Square;
+
+impl Lattice for Square {
+    type Symmetry = Symmetry;
+
+    const ID: u8 = 0;
+    const NAME: &'static str = "square";
+    const ROTATIONS: usize = 4;
+
+    fn neighbours((x, y): (isize, isize)) -> Vec<(isize, isize)> {
+        vec![(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+    }
+
+    fn symmetries(mode: Mode) -> Vec<Symmetry> {
+        match mode {
+            Mode::Fixed => Symmetry::ALL[..1].to_vec(),
+            Mode::OneSided => Symmetry::ALL[..4].to_vec(),
+            Mode::Free => Symmetry::ALL.to_vec(),
+        }
+    }
+
+    fn transform((x, y): (isize, isize), s: Symmetry) -> (isize, isize) {
+        let x = if s.mirror { -x } else { x };
+        (0..s.rotation as usize).fold((x, y), |(x, y), _| (-y, x))
+    }
+
+    fn largest_grid(cells: usize, _mode: Mode) -> usize {
+        // The spans along X and Y add up to at most cells - 1, and the turns swap them
+        (0..cells)
+            .map(|a| (a + 3) * (cells - 1 - a + 3))
+            .max()
+            .unwrap()
+    }
+
+    fn seeds<T: Grid>(_mode: Mode) -> Vec<Polyomino<T>> {
+        vec![Polyomino::trivial()]
+    }
+
+    fn add_square<T: Grid>(p: &mut Polyomino<T>, x: usize, y: usize, anti_mask: &T) {
+        p.add_square(x, y, anti_mask);
+    }
+
+    fn decline_within<T, F>(p: &Polyomino<T>, fits: F) -> Vec<Polyomino<T>>
+    where
+        T: Grid,
+        F: Fn((usize, usize)) -> bool,
+    {
+        decline_within(p, fits)
+    }
+
+    fn apply<T: Grid>(p: &Polyomino<T>, s: Symmetry) -> Polyomino<T> {
+        Polyomino {
+            square_count: p.square_count,
+            dimension: s.apply_dim(p.dimension),
+            repr: p.repr.transform(p.dimension, s),
+            mask: p.mask.transform(p.dimension, s),
+        }
+    }
+
+    fn canonical<T: Grid>(p: Polyomino<T>, mode: Mode) -> (Polyomino<T>, Symmetry) {
+        polyomino::canonical(p, mode)
+    }
+
+    fn symmetry_class<T: Grid>(p: &Polyomino<T>) -> Option<SymmetryClass> {
+        Some(polyomino::symmetry_class(p))
+    }
+
+    fn has_holes<T: Grid>(p: &Polyomino<T>) -> bool {
+        p.has_holes()
+    }
+}
+
+/// The triangular lattice of polyiamonds, with three neighbours and six rotations
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Triangular;
+
+impl Lattice for Triangular {
+    type Symmetry = Turn;
+
+    const ID: u8 = 1;
+    const NAME: &'static str = "triangular";
+    const ROTATIONS: usize = 6;
+    const PERIOD: isize = 2;
+
+    fn neighbours((x, y): (isize, isize)) -> Vec<(isize, isize)> {
+        if x.rem_euclid(2) == 0 {
+            vec![(x - 1, y), (x + 1, y), (x + 1, y - 1)]
+        } else {
+            vec![(x - 1, y), (x + 1, y), (x - 1, y + 1)]
+        }
+    }
+
+    fn symmetries(mode: Mode) -> Vec<Turn> {
+        Turn::all(Self::ROTATIONS, mode)
+    }
+
+    fn transform((x, y): (isize, isize), s: Turn) -> (isize, isize) {
+        // Three times the coordinates of the centre of the triangle in the sheared lattice,
+        // which are 1 modulo 3 for the triangles pointing up and 2 for the other ones
+        let down = x.rem_euclid(2);
+        let centre = (3 * x.div_euclid(2) + 1 + down, 3 * y + 1 + down);
+        let centre = if s.mirror {
+            (centre.1, centre.0)
+        } else {
+            centre
+        };
+        let (a, b) = (0..s.rotation).fold(centre, |(a, b), _| (-b, a + b));
+        let down = (a.rem_euclid(3) == 2) as isize;
+        (2 * ((a - 1 - down) / 3) + down, (b - 1 - down) / 3)
+    }
+
+    fn largest_grid(cells: usize, mode: Mode) -> usize {
+        // A side is crossed by one of the lines, and (u, v, u + v + d) are the lines on
+        // the left of the triangle (2u + d, v). A span of a across the first family gives
+        // a width between 2a + 2 and 2a + 5, because of the margin of one or two elements
+        let width = |a: usize| ((2 * a + 2).max(3), 2 * a + 5);
+        let mut largest = 0;
+        for spans in spans(cells, 1, 1) {
+            let orientations = orientations(spans, mode);
+            // The representative is at most as wide as the narrowest bound, and only the
+            // orientations which may be that narrow can be the representative
+            let narrowest = orientations.iter().map(|&(a, _)| width(a).1).min().unwrap();
+            let area = orientations
+                .iter()
+                .filter(|&&(a, _)| width(a).0 <= narrowest)
+                .map(|&(a, b)| width(a).1.min(narrowest) * (b + 3))
+                .max()
+                .unwrap();
+            largest = largest.max(area);
+        }
+        largest
+    }
+}
+
+/// The hexagonal lattice of polyhexes, with six neighbours and six rotations
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Hexagonal;
+
+impl Lattice for Hexagonal {
+    type Symmetry = Turn;
+
+    const ID: u8 = 2;
+    const NAME: &'static str = "hexagonal";
+    const ROTATIONS: usize = 6;
+
+    fn neighbours((x, y): (isize, isize)) -> Vec<(isize, isize)> {
+        vec![
+            (x - 1, y),
+            (x + 1, y),
+            (x, y - 1),
+            (x, y + 1),
+            (x + 1, y - 1),
+            (x - 1, y + 1),
+        ]
+    }
+
+    fn symmetries(mode: Mode) -> Vec<Turn> {
+        Turn::all(Self::ROTATIONS, mode)
+    }
+
+    fn transform((x, y): (isize, isize), s: Turn) -> (isize, isize) {
+        let cell = if s.mirror { (y, x) } else { (x, y) };
+        (0..s.rotation).fold(cell, |(x, y), _| (-y, x + y))
+    }
+
+    fn largest_grid(cells: usize, mode: Mode) -> usize {
+        // A side is crossed by two of the lines of constant x, y and x + y, and the
+        // representative is the narrowest image, then the lowest
+        spans(cells, 2, 0)
+            .into_iter()
+            .map(|spans| {
+                let (a, b) = orientations(spans, mode).into_iter().min().unwrap();
+                (a + 3) * (b + 3)
+            })
+            .max()
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        board::Board,
+        database::Database,
+        grids::{block_grid::BlockGrid, naive::Naive},
+    };
+
+    use super::*;
+
+    /// The square lattice without its fast implementations
+    #[derive(Copy, Clone, Debug, Default)]
+    struct Slow;
+
+    impl Lattice for Slow {
+        type Symmetry = Symmetry;
+
+        const ID: u8 = Square::ID;
+        const NAME: &'static str = "slow";
+        const ROTATIONS: usize = Square::ROTATIONS;
+
+        fn neighbours(cell: (isize, isize)) -> Vec<(isize, isize)> {
+            Square::neighbours(cell)
+        }
+
+        fn symmetries(mode: Mode) -> Vec<Symmetry> {
+            Square::symmetries(mode)
+        }
+
+        fn transform(cell: (isize, isize), s: Symmetry) -> (isize, isize) {
+            Square::transform(cell, s)
+        }
+
+        fn largest_grid(cells: usize, mode: Mode) -> usize {
+            Square::largest_grid(cells, mode)
+        }
+    }
+
+    /// The database of the polyforms with up to `n` cells
+    fn database<T, L>(mode: Mode, n: usize) -> Database<T, L>
+    where
+        T: Grid + std::ops::BitOrAssign,
+        L: Lattice,
+    {
+        let mut db = Database::<T, L>::with_mode(mode);
+        while db.counts().len() < n {
+            db.expand_level(2);
+        }
+        db
+    }
+
+    fn counts<T, L>(mode: Mode, n: usize) -> Vec<u128>
+    where
+        T: Grid + std::ops::BitOrAssign,
+        L: Lattice,
+    {
+        database::<T, L>(mode, n).counts().copied().collect()
+    }
+
+    #[test]
+    fn neighbours_are_symmetric() {
+        fn check<L: Lattice>() {
+            let all: Vec<_> = (-4..4).flat_map(|x| (-4..4).map(move |y| (x, y))).collect();
+            for &cell in all.iter() {
+                let neighbours = L::neighbours(cell);
+                for &n in neighbours.iter() {
+                    assert!(L::neighbours(n).contains(&cell), "{cell:?}");
+                }
+                // The symmetries are isometries of the lattice
+                for s in L::symmetries(Mode::Free) {
+                    let image = L::transform(cell, s);
+                    for &n in neighbours.iter() {
+                        assert!(L::neighbours(image).contains(&L::transform(n, s)));
+                    }
+                }
+            }
+
+            let images = |mode| -> Vec<Vec<_>> {
+                L::symmetries(mode)
+                    .into_iter()
+                    .map(|s| all.iter().map(|&cell| L::transform(cell, s)).collect())
+                    .collect()
+            };
+            assert_eq!(images(Mode::Fixed).len(), 1);
+            assert_eq!(images(Mode::OneSided).len(), L::ROTATIONS);
+            let free = images(Mode::Free);
+            assert_eq!(free.len(), 2 * L::ROTATIONS);
+            assert_eq!(free[0], all);
+            for (i, image) in free.iter().enumerate() {
+                assert!(free[..i].iter().all(|other| other != image));
+            }
+        }
+        check::<Square>();
+        check::<Triangular>();
+        check::<Hexagonal>();
+    }
+
+    #[test]
+    fn square_symmetries() {
+        // The symmetries of the cells agree with the ones of the grids
+        let db = database::<Naive, Square>(Mode::Fixed, 5);
+        for p in db.to_queue().iter() {
+            for s in Square::symmetries(Mode::Free) {
+                let (fast, slow) = (Square::apply(p, s), Slow::apply(p, s));
+                assert_eq!(fast.dimension, slow.dimension);
+                assert!(fast.repr == slow.repr && fast.mask == slow.mask);
+            }
+        }
+    }
+
+    #[test]
+    fn anti_masks() {
+        // The slow path generates no more redundant polyominoes than the square one,
+        // from other representatives with other merged masks
+        let slow = database::<Naive, Slow>(Mode::Free, 8);
+        let square = database::<Naive, Square>(Mode::Free, 8);
+        assert!(slow.counts().eq(square.counts()));
+        assert!(slow.stats().zip(square.stats()).all(|(s, q)| s <= q));
+    }
+
+    #[test]
+    fn polyominoes() {
+        assert_eq!(
+            counts::<Naive, Slow>(Mode::Fixed, 7),
+            [1, 2, 6, 19, 63, 216, 760]
+        );
+        assert_eq!(
+            counts::<BlockGrid, Slow>(Mode::OneSided, 7),
+            [1, 1, 2, 7, 18, 60, 196]
+        );
+        assert_eq!(
+            counts::<Board, Slow>(Mode::Free, 7),
+            [1, 1, 2, 5, 12, 35, 108]
+        );
+    }
+
+    #[test]
+    fn polyiamonds() {
+        assert_eq!(
+            counts::<BlockGrid, Triangular>(Mode::Fixed, 9),
+            [2, 3, 6, 14, 36, 94, 250, 675, 1838]
+        );
+        assert_eq!(
+            counts::<Naive, Triangular>(Mode::OneSided, 9),
+            [1, 1, 1, 4, 6, 19, 43, 120, 307]
+        );
+        assert_eq!(
+            counts::<Board, Triangular>(Mode::Free, 10),
+            [1, 1, 1, 3, 4, 12, 24, 66, 160, 448]
+        );
+    }
+
+    #[test]
+    fn polyhexes() {
+        assert_eq!(
+            counts::<Board, Hexagonal>(Mode::Fixed, 7),
+            [1, 3, 11, 44, 186, 814, 3652]
+        );
+        assert_eq!(
+            counts::<BlockGrid, Hexagonal>(Mode::OneSided, 7),
+            [1, 1, 3, 10, 33, 147, 620]
+        );
+        assert_eq!(
+            counts::<Naive, Hexagonal>(Mode::Free, 8),
+            [1, 1, 3, 7, 22, 82, 333, 1448]
+        );
+
+        // The ring of six hexagons is the first polyhex with a hole
+        let db = database::<Board, Hexagonal>(Mode::Free, 6);
+        let hole_free: Vec<_> = db.hole_free().copied().collect();
+        assert_eq!(hole_free, [1, 1, 3, 7, 22, 81]);
+        assert!(db.classes().all(|classes| classes.iter().all(|&c| c == 0)));
+    }
+
+    #[test]
+    fn same_representatives_for_all_backends() {
+        let board = free_polyiamonds::<Board>();
+        assert_eq!(board, free_polyiamonds::<Naive>());
+        assert_eq!(board, free_polyiamonds::<BlockGrid>());
+    }
+
+    /// The cells of the representatives of the free polyiamonds with six triangles
+    fn free_polyiamonds<T: Grid + std::ops::BitOrAssign>() -> Vec<Vec<(usize, usize)>> {
+        let db = database::<T, Triangular>(Mode::Free, 6);
+        let mut cells: Vec<_> = db.to_queue().iter().map(|p| p.cells()).collect();
+        cells.sort();
+        cells
+    }
+
+    /// The area of the grid of the representative of the polyform for the mode
+    fn area<L: Lattice>(cells: Vec<(isize, isize)>, mode: Mode) -> usize {
+        let (p, _) = L::canonical(build::<Board, L>(cells, vec![]), mode);
+        p.dimension.0 as usize * p.dimension.1 as usize
+    }
+
+    /// The hexagons of three arms of the given lengths from the origin, along directions
+    /// at 120 degrees, turning counterclockwise or clockwise
+    fn tripod(arms: [isize; 3], counterclockwise: bool) -> Vec<(isize, isize)> {
+        let directions = match counterclockwise {
+            true => [(1, 0), (-1, 1), (0, -1)],
+            false => [(0, 1), (1, -1), (-1, 0)],
+        };
+        let mut cells = vec![(0, 0)];
+        for (length, (dx, dy)) in arms.into_iter().zip(directions) {
+            cells.extend((1..=length).map(|i| (i * dx, i * dy)));
+        }
+        cells
+    }
+
+    #[test]
+    fn largest_grids() {
+        // The bounds hold for the enumerated polyforms
+        fn check<L: Lattice>(n: usize) {
+            for mode in [Mode::Fixed, Mode::OneSided, Mode::Free] {
+                let db = database::<Board, L>(mode, n);
+                for p in db.to_queue().iter() {
+                    let area = p.dimension.0 as usize * p.dimension.1 as usize;
+                    assert!(area <= L::largest_grid(p.square_count as usize, mode));
+                }
+            }
+        }
+        check::<Square>(8);
+        check::<Triangular>(9);
+        check::<Hexagonal>(7);
+
+        assert_eq!(Square::max_cells(Mode::Free), 22);
+        assert_eq!(Square::max_cells(Mode::Fixed), 22);
+
+        // The tripod of 18 hexagons with arms 6, 6 and 5 does not fit in any orientation
+        assert_eq!(Hexagonal::max_cells(Mode::Free), 17);
+        assert!(area::<Hexagonal>(tripod([6, 6, 5], true), Mode::Free) > Packed::MAX_AREA);
+        // Without the reflections, the narrowest image of one of the tripods of
+        // 17 hexagons with arms 7, 1 and 8 is long
+        assert_eq!(Hexagonal::max_cells(Mode::OneSided), 16);
+        let areas = [true, false].map(|c| area::<Hexagonal>(tripod([7, 1, 8], c), Mode::OneSided));
+        assert!(areas.iter().any(|&area| area > Packed::MAX_AREA));
+        // A diagonal line of 12 hexagons cannot be turned
+        assert_eq!(Hexagonal::max_cells(Mode::Fixed), 11);
+        let line = (0..12).map(|i| (i, -i)).collect();
+        assert!(area::<Hexagonal>(line, Mode::Fixed) > Packed::MAX_AREA);
+
+        assert_eq!(Triangular::max_cells(Mode::Free), 20);
+        assert_eq!(Triangular::max_cells(Mode::Fixed), 15);
+    }
+}
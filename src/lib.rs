@@ -1,6 +1,19 @@
 pub mod board;
+pub mod cpu;
 pub mod database;
+pub mod domino;
+pub mod encoding;
+pub mod enumerator;
 pub mod grid;
 pub mod grids;
+pub mod identify;
+pub mod lattice;
+pub mod packed;
 pub mod polyomino;
+pub mod properties;
+pub mod puzzle;
+pub mod rank;
+pub mod redelmeier;
 pub mod rotation;
+pub mod tiling;
+pub mod transfer_matrix;
@@ -0,0 +1,184 @@
+//! Compact storage of the grids of a known bounding box, independently of their backend.
+//! The bits of the bounding box are stored column by column in a small inline array,
+//! in the same order as [`crate::encoding::write_grid`].
+
+use std::{
+    io::{self, Read, Write},
+    ops::BitOrAssign,
+};
+
+use crate::{encoding::invalid_data, grid::Grid, polyomino::Polyomino};
+
+const WORDS: usize = 3;
+
+/// The bits of a bounding box of at most [`Packed::MAX_AREA`] elements.
+/// With its margin, a polyomino of n squares has a bounding box of at most
+/// ((n + 5) / 2)² elements, so polyominoes of up to 22 squares fit
+#[derive(Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Packed([u64; WORDS]);
+
+impl Packed {
+    pub const MAX_AREA: usize = 64 * WORDS;
+
+    /// Pack the elements of the grid inside the bounding box of dimension `dim`
+    pub fn pack<T: Grid>(grid: &T, dim: (u8, u8)) -> Self {
+        let area = dim.0 as usize * dim.1 as usize;
+        assert!(
+            area <= Self::MAX_AREA,
+            "A bounding box of dimension {dim:?} cannot be packed"
+        );
+
+        let mut words = [0; WORDS];
+        let mut i = 0;
+        for x in 0..dim.0 as usize {
+            for y in 0..dim.1 as usize {
+                if grid.get(x, y) {
+                    words[i / 64] |= 1 << (i % 64);
+                }
+                i += 1;
+            }
+        }
+        Self(words)
+    }
+
+    /// Expand into a grid, with the space of the bounding box reserved
+    pub fn unpack<T: Grid>(&self, dim: (u8, u8)) -> T {
+        let mut grid = T::new();
+        grid.reserve_space(dim.0 as usize, dim.1 as usize);
+        let mut i = 0;
+        for x in 0..dim.0 as usize {
+            for y in 0..dim.1 as usize {
+                if self.0[i / 64] & (1 << (i % 64)) != 0 {
+                    grid.set(x, y);
+                }
+                i += 1;
+            }
+        }
+        grid
+    }
+
+    pub fn count(&self) -> u32 {
+        self.0.iter().map(|w| w.count_ones()).sum()
+    }
+
+    /// Write the bits of the bounding box, in the format of [`crate::encoding::write_grid`]
+    pub fn write<W: Write>(&self, w: &mut W, dim: (u8, u8)) -> io::Result<()> {
+        let len = (dim.0 as usize * dim.1 as usize).div_ceil(8);
+        let bytes: Vec<u8> = self.0.iter().flat_map(|w| w.to_le_bytes()).collect();
+        w.write_all(&bytes[..len])
+    }
+
+    /// Read bits written by [`Packed::write`] or [`crate::encoding::write_grid`]
+    pub fn read<R: Read>(r: &mut R, dim: (u8, u8)) -> io::Result<Self> {
+        let area = dim.0 as usize * dim.1 as usize;
+        if area > Self::MAX_AREA {
+            return Err(invalid_data("The bounding box is too large to be packed"));
+        }
+
+        let mut bytes = [0; 8 * WORDS];
+        r.read_exact(&mut bytes[..area.div_ceil(8)])?;
+        let mut words = [0; WORDS];
+        for (i, word) in words.iter_mut().enumerate() {
+            *word = u64::from_le_bytes(bytes[8 * i..8 * (i + 1)].try_into().unwrap());
+            // Ignore the padding of the last byte
+            let bits = area.saturating_sub(64 * i).min(64);
+            if bits < 64 {
+                *word &= (1 << bits) - 1;
+            }
+        }
+        Ok(Self(words))
+    }
+}
+
+impl BitOrAssign for Packed {
+    fn bitor_assign(&mut self, rhs: Self) {
+        for (a, b) in self.0.iter_mut().zip(rhs.0.iter()) {
+            *a |= b;
+        }
+    }
+}
+
+/// A polyomino with packed grids, as stored by the database
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct PackedPolyomino {
+    pub dimension: (u8, u8),
+    pub repr: Packed,
+    pub mask: Packed,
+}
+
+impl PackedPolyomino {
+    pub fn pack<T: Grid>(p: &Polyomino<T>) -> Self {
+        Self {
+            dimension: p.dimension,
+            repr: Packed::pack(&p.repr, p.dimension),
+            mask: Packed::pack(&p.mask, p.dimension),
+        }
+    }
+
+    pub fn unpack<T: Grid>(&self) -> Polyomino<T> {
+        Polyomino {
+            square_count: self.repr.count() as u8,
+            dimension: self.dimension,
+            repr: self.repr.unpack(self.dimension),
+            mask: self.mask.unpack(self.dimension),
+        }
+    }
+
+    pub fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(&[self.dimension.0, self.dimension.1])?;
+        self.repr.write(w, self.dimension)?;
+        self.mask.write(w, self.dimension)
+    }
+
+    pub fn read<R: Read>(r: &mut R) -> io::Result<Self> {
+        let mut dimension = [0; 2];
+        r.read_exact(&mut dimension)?;
+        let dimension = (dimension[0], dimension[1]);
+        Ok(Self {
+            dimension,
+            repr: Packed::read(r, dimension)?,
+            mask: Packed::read(r, dimension)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        encoding::write_grid,
+        grid::are_equal,
+        grids::{block_grid::BlockGrid, naive::Naive},
+    };
+
+    use super::*;
+
+    #[test]
+    fn pack_round_trip() {
+        let dim = (13, 14);
+        let mut grid = BlockGrid::new();
+        grid.reserve_space(dim.0 as usize, dim.1 as usize);
+        for (x, y) in [(0, 0), (12, 13), (5, 7), (6, 7), (12, 0)] {
+            grid.set(x, y);
+        }
+
+        let packed = Packed::pack(&grid, dim);
+        assert_eq!(packed.count(), 5);
+        are_equal(&packed.unpack::<Naive>(dim), &grid);
+
+        // Same bits as the grid encoding
+        let mut encoded = vec![];
+        write_grid(&mut encoded, &grid, dim).unwrap();
+        let mut written = vec![];
+        packed.write(&mut written, dim).unwrap();
+        assert_eq!(encoded, written);
+        assert_eq!(Packed::read(&mut written.as_slice(), dim).unwrap(), packed);
+    }
+
+    #[test]
+    fn largest_polyominoes_fit() {
+        // Area of the bounding box with its margin, of n squares and width w
+        let area = |n: usize, w: usize| (w + 2) * (n + 1 - w + 2);
+        assert!((1..=22).all(|w| area(22, w) <= Packed::MAX_AREA));
+        assert!(area(23, 12) > Packed::MAX_AREA);
+    }
+}
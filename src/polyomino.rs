@@ -1,7 +1,83 @@
-use std::fmt::{self, Debug};
+use std::fmt::{self, Debug, Display};
+use std::str::FromStr;
 
 use crate::grid::Grid;
-use crate::rotation::Rotation;
+use crate::rotation::{Rotation, Symmetry, SymmetryClass};
+
+/// Which transformations identify two polyominoes. Translations are always identified
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Mode {
+    /// Polyominoes are only identified up to translations
+    Fixed,
+    /// Polyominoes are identified up to rotations
+    OneSided,
+    /// Polyominoes are identified up to rotations and reflections
+    Free,
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fixed" => Ok(Mode::Fixed),
+            "one-sided" => Ok(Mode::OneSided),
+            "free" => Ok(Mode::Free),
+            _ => Err(format!(
+                "Unknown mode {s}, expected fixed, one-sided or free"
+            )),
+        }
+    }
+}
+
+impl Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Mode::Fixed => "fixed",
+            Mode::OneSided => "one-sided",
+            Mode::Free => "free",
+        })
+    }
+}
+
+/// A window of `width` × `height` squares the polyominoes must fit in. Polyominoes
+/// identified up to rotations may also fit it once turned by a quarter
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Window {
+    pub width: u8,
+    pub height: u8,
+}
+
+impl Window {
+    /// Whether a bounding box of `size` squares, without the margin, fits in the window
+    /// in one of the orientations allowed by the mode
+    pub fn fits(&self, size: (usize, usize), mode: Mode) -> bool {
+        let (width, height) = (self.width as usize, self.height as usize);
+        (size.0 <= width && size.1 <= height)
+            || (mode != Mode::Fixed && size.0 <= height && size.1 <= width)
+    }
+}
+
+impl FromStr for Window {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("Invalid window {s}, expected WIDTHxHEIGHT");
+        let (width, height) = s.split_once(['x', 'X']).ok_or_else(error)?;
+        let width: u8 = width.trim().parse().map_err(|_| error())?;
+        let height: u8 = height.trim().parse().map_err(|_| error())?;
+        if width == 0 || height == 0 {
+            return Err(error());
+        }
+        Ok(Window { width, height })
+    }
+}
+
+impl Display for Window {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
 
 #[derive(Copy, Clone)]
 pub struct Polyomino<T>
@@ -93,6 +169,179 @@ where
         }
     }
 
+    /// Create a polyomino from the coordinates of its squares, which should be connected.
+    /// The squares are translated against the origin, with the usual margin of one
+    /// element around them, and all neighbours of the squares are in the mask
+    pub fn from_cells(cells: &[(usize, usize)]) -> Self {
+        assert!(
+            !cells.is_empty(),
+            "There are no polyominoes with zero square"
+        );
+
+        let min_x = cells.iter().map(|&(x, _)| x).min().unwrap();
+        let min_y = cells.iter().map(|&(_, y)| y).min().unwrap();
+        let max_x = cells.iter().map(|&(x, _)| x).max().unwrap();
+        let max_y = cells.iter().map(|&(_, y)| y).max().unwrap();
+        let dimension = ((max_x - min_x + 3) as u8, (max_y - min_y + 3) as u8);
+
+        let mut repr = T::new();
+        repr.reserve_space(dimension.0 as usize, dimension.1 as usize);
+        for &(x, y) in cells.iter() {
+            repr.set(x - min_x + 1, y - min_y + 1);
+        }
+
+        let mut mask = T::new();
+        mask.reserve_space(dimension.0 as usize, dimension.1 as usize);
+        for &(x, y) in cells.iter() {
+            let (x, y) = (x - min_x + 1, y - min_y + 1);
+            for (x, y) in [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)].into_iter() {
+                if !repr.get(x, y) {
+                    mask.set(x, y);
+                }
+            }
+        }
+
+        Self::from(dimension, repr, mask)
+    }
+
+    /// Return the coordinates of the squares, relative to the bounding box
+    /// of the squares (i.e. without the margin)
+    pub fn cells(&self) -> Vec<(usize, usize)> {
+        let mut cells = vec![];
+        for y in 1..self.dimension.1 as usize - 1 {
+            for x in 1..self.dimension.0 as usize - 1 {
+                if self.repr.get(x, y) {
+                    cells.push((x - 1, y - 1));
+                }
+            }
+        }
+        cells
+    }
+
+    /// Return the dimension of the bounding box of the squares (i.e. without the margin)
+    pub fn size(&self) -> (usize, usize) {
+        (self.dimension.0 as usize - 2, self.dimension.1 as usize - 2)
+    }
+
+    /// Return the cells of each hole, that is of each component of the empty cells,
+    /// connected by their sides, which is enclosed by the squares.
+    /// Coordinates are relative to the bounding box of the squares, like [`Polyomino::cells`]
+    pub fn holes(&self) -> Vec<Vec<(usize, usize)>> {
+        let (width, height) = (self.dimension.0 as usize, self.dimension.1 as usize);
+        let mut seen = vec![false; width * height];
+        for y in 0..height {
+            for x in 0..width {
+                seen[y * width + x] = self.repr.get(x, y);
+            }
+        }
+
+        // Visit the component of (x, y) among the cells not seen yet
+        let fill = |x: usize, y: usize, seen: &mut Vec<bool>| {
+            let mut component = vec![];
+            let mut stack = vec![(x, y)];
+            seen[y * width + x] = true;
+            while let Some((x, y)) = stack.pop() {
+                component.push((x, y));
+                let neighbours = [
+                    (x.wrapping_sub(1), y),
+                    (x + 1, y),
+                    (x, y.wrapping_sub(1)),
+                    (x, y + 1),
+                ];
+                for (x, y) in neighbours.into_iter() {
+                    if x < width && y < height && !seen[y * width + x] {
+                        seen[y * width + x] = true;
+                        stack.push((x, y));
+                    }
+                }
+            }
+            component
+        };
+
+        // The margin is empty, and reaches all cells outside of the polyomino
+        fill(0, 0, &mut seen);
+
+        let mut holes = vec![];
+        for y in 1..height - 1 {
+            for x in 1..width - 1 {
+                if !seen[y * width + x] {
+                    let mut hole: Vec<_> = fill(x, y, &mut seen)
+                        .into_iter()
+                        .map(|(x, y)| (x - 1, y - 1))
+                        .collect();
+                    hole.sort_by_key(|&(x, y)| (y, x));
+                    holes.push(hole);
+                }
+            }
+        }
+        holes
+    }
+
+    /// Return whether the polyomino has a hole, like [`Polyomino::holes`] without listing
+    /// them. The outside is filled row by row on bitboards, from the empty margin,
+    /// until it stops growing, and the polyominoes of fewer than seven squares have none
+    pub fn has_holes(&self) -> bool {
+        let (width, height) = (self.dimension.0 as usize, self.dimension.1 as usize);
+        if self.square_count < 7 {
+            return false;
+        }
+        if width > 64 || height > 64 {
+            return !self.holes().is_empty();
+        }
+
+        // Bit x of the rows is the cell (x, y)
+        let mut empty = [0u64; 64];
+        for (y, row) in empty.iter_mut().enumerate().take(height) {
+            for x in 0..width {
+                if !self.repr.get(x, y) {
+                    *row |= 1 << x;
+                }
+            }
+        }
+        let mut outside = [0u64; 64];
+        outside[0] = empty[0];
+        outside[height - 1] = empty[height - 1];
+
+        // Spread the outside from the rows above and below, and then along the row
+        let spread = |outside: &mut [u64; 64], y: usize| {
+            let mut row = outside[y];
+            if y > 0 {
+                row |= outside[y - 1];
+            }
+            if y + 1 < height {
+                row |= outside[y + 1];
+            }
+            row &= empty[y];
+            loop {
+                let next = (row | row << 1 | row >> 1) & empty[y];
+                if next == row {
+                    break;
+                }
+                row = next;
+            }
+            let grown = row != outside[y];
+            outside[y] = row;
+            grown
+        };
+        loop {
+            let mut grown = false;
+            for y in 1..height - 1 {
+                grown |= spread(&mut outside, y);
+            }
+            for y in (1..height - 1).rev() {
+                grown |= spread(&mut outside, y);
+            }
+            if !grown {
+                break;
+            }
+        }
+
+        (1..height - 1).any(|y| empty[y] != outside[y])
+    }
+
+    /// Add a square in the mask at (x, y), growing the bounding box if needed.
+    /// Its neighbours join the mask, except the ones in the anti-mask,
+    /// whose coordinates are the ones before the bounding box grows
     pub fn add_square(&mut self, mut x: usize, mut y: usize, anti_mask: &T) {
         self.square_count += 1;
         // The anti_mask is not shifted like the repr and mask boards, for efficiency reasons
@@ -150,45 +399,88 @@ where
     }
 }
 
-/// Return all polyominoes that can be created by adding a square to this polyomino, excluding positions out of the mask
+/// Return all polyominoes that can be created by adding a square to this polyomino, excluding positions out of the mask.
+/// The squares c1, c2, ... of the mask are added in order of coordinates, see [`decline_in_order`].
+/// The masks of a child only depend on its parent, and the masks of the duplicates of a child
+/// are merged with a union in [`crate::database::Database::register`]. Children are only
+/// declined once all polyominoes of their level are registered, so the merged masks,
+/// and the counts, do not depend on the order in which polyominoes are processed
 pub fn decline<T>(p: &Polyomino<T>) -> Vec<Polyomino<T>>
 where
     T: Grid,
 {
-    let mut polyominoes = vec![];
+    decline_within(p, |_| true)
+}
 
-    let mut mask = p.mask.clone();
-    let mut anti_mask = T::new();
-    anti_mask.reserve_space((p.dimension.0 + 1) as usize, (p.dimension.1 + 1) as usize);
-    // println!("Mask is\n{:?}\nWitness mask is\n{:?}", mask, mask_witness);
+/// Same as [`decline`], without the polyominoes whose bounding box, without the margin,
+/// does not `fit`. They are skipped before being built, and the masks of the others
+/// are unchanged, so as long as `fit` accepts the smaller bounding boxes of the ones it
+/// accepts, this enumerates exactly the polyominoes that fit
+pub fn decline_within<T, F>(p: &Polyomino<T>, fits: F) -> Vec<Polyomino<T>>
+where
+    T: Grid,
+    F: Fn((usize, usize)) -> bool,
+{
+    let mut order = vec![];
     for x in 0..p.dimension.0 as usize {
         for y in 0..p.dimension.1 as usize {
             if p.mask.get(x, y) {
-                // add a new polyomino
-                let mut new_p = Polyomino {
-                    square_count: p.square_count,
-                    dimension: p.dimension,
-                    repr: p.repr.clone(),
-                    mask: mask.clone(),
-                };
-                new_p.add_square(x, y, &anti_mask);
-                anti_mask.set(x, y);
-                mask.unset(x, y);
-
-                polyominoes.push(new_p);
+                order.push((x, y));
             }
         }
     }
+    decline_in_order(p, fits, &order)
+}
+
+/// Same as [`decline_within`], adding the squares c1, c2, ... of the mask in the given order,
+/// which lists each of them once. The polyomino with ci keeps the squares of the mask after ci,
+/// and gets the neighbours of ci except c1, ..., ci-1: the polyomino with both cj and ci,
+/// for j < i, is left to the one with cj, which has ci in its mask. Only the masks depend
+/// on the order, the polyominoes enumerated from merged masks being the same for any order
+pub fn decline_in_order<T, F>(
+    p: &Polyomino<T>,
+    fits: F,
+    order: &[(usize, usize)],
+) -> Vec<Polyomino<T>>
+where
+    T: Grid,
+    F: Fn((usize, usize)) -> bool,
+{
+    let mut polyominoes = vec![];
+    let (width, height) = p.size();
+
+    let mut mask = p.mask.clone();
+    let mut anti_mask = T::new();
+    anti_mask.reserve_space((p.dimension.0 + 1) as usize, (p.dimension.1 + 1) as usize);
+    for &(x, y) in order.iter() {
+        debug_assert!(
+            p.mask.get(x, y),
+            "Only the squares of the mask can be added"
+        );
+        // The square is in the margin when it grows the bounding box
+        let grows_x = x == 0 || x + 1 == p.dimension.0 as usize;
+        let grows_y = y == 0 || y + 1 == p.dimension.1 as usize;
+        if fits((width + grows_x as usize, height + grows_y as usize)) {
+            // add a new polyomino
+            let mut new_p = Polyomino {
+                square_count: p.square_count,
+                dimension: p.dimension,
+                repr: p.repr.clone(),
+                mask: mask.clone(),
+            };
+            new_p.add_square(x, y, &anti_mask);
+            polyominoes.push(new_p);
+        }
+        anti_mask.set(x, y);
+        mask.unset(x, y);
+    }
 
-    // FIXME: doesn't work when reordering polyominoes,
-    // due to the mask being more generic for the first ones generated
-    // IDEA: reset the mask if the polyomino was not seen before (but the mask would not be useful then)
-    //      Or take the mask with the higher count among rotations,
-    //      but this means that all polyomnioes of some square count must be processed before starting the next square count
-    // polyominoes.reverse();
     polyominoes
 }
 
+/// Return the smallest rotation of the polyomino, the narrowest first and then by
+/// [`Grid::cmp_rows`], so that the representative does not depend on the backend,
+/// with the rotation mapping the polyomino to it
 pub fn smallest_rotation<T>(p: Polyomino<T>) -> (Polyomino<T>, Rotation)
 where
     T: Grid,
@@ -197,7 +489,7 @@ where
     if p.dimension.0 < p.dimension.1 {
         // Only compare rotation 0 and rotation 180
         let rotated_board = p.repr.rotate(p.dimension, Rotation::R180);
-        if p.repr < rotated_board {
+        if p.repr.cmp_rows(&rotated_board, p.dimension).is_lt() {
             (p, Rotation::R0)
         } else {
             (
@@ -214,11 +506,12 @@ where
         // Compare rotations 90 and 270
         let board_90 = p.repr.rotate(p.dimension, Rotation::R90);
         let board_270 = p.repr.rotate(p.dimension, Rotation::R270);
-        if board_90 < board_270 {
+        let dimension = (p.dimension.1, p.dimension.0);
+        if board_90.cmp_rows(&board_270, dimension).is_lt() {
             (
                 Polyomino {
                     square_count: p.square_count,
-                    dimension: (p.dimension.1, p.dimension.0),
+                    dimension,
                     repr: board_90,
                     mask: p.mask.rotate(p.dimension, Rotation::R90),
                 },
@@ -228,7 +521,7 @@ where
             (
                 Polyomino {
                     square_count: p.square_count,
-                    dimension: (p.dimension.1, p.dimension.0),
+                    dimension,
                     repr: board_270,
                     mask: p.mask.rotate(p.dimension, Rotation::R270),
                 },
@@ -244,14 +537,14 @@ where
 
         // Comparison by pairs : (p, board_180) and (board_90, board_270)
         let (smallest_1, rot1) = {
-            if p.repr < board_180 {
+            if p.repr.cmp_rows(&board_180, p.dimension).is_lt() {
                 (p.repr, Rotation::R0)
             } else {
                 (board_180, Rotation::R180)
             }
         };
         let (smallest_2, rot2) = {
-            if board_90 < board_270 {
+            if board_90.cmp_rows(&board_270, p.dimension).is_lt() {
                 (board_90, Rotation::R90)
             } else {
                 (board_270, Rotation::R270)
@@ -259,7 +552,7 @@ where
         };
 
         // Then compare the smallest of each pair
-        if smallest_1 < smallest_2 {
+        if smallest_1.cmp_rows(&smallest_2, p.dimension).is_lt() {
             (
                 Polyomino {
                     square_count: p.square_count,
@@ -283,13 +576,101 @@ where
     }
 }
 
+/// Same as [`smallest_rotation`], but among the eight symmetries of the square,
+/// so that mirror images share the same representative
+pub fn smallest_symmetry<T>(p: Polyomino<T>) -> (Polyomino<T>, Symmetry)
+where
+    T: Grid,
+{
+    // Take smallest dimension first, which leaves four candidates
+    // unless the dimensions are equal
+    let candidates: &[Symmetry] = if p.dimension.0 < p.dimension.1 {
+        &[
+            Symmetry::rotation(Rotation::R0),
+            Symmetry::rotation(Rotation::R180),
+            Symmetry::reflection(Rotation::R0),
+            Symmetry::reflection(Rotation::R180),
+        ]
+    } else if p.dimension.0 > p.dimension.1 {
+        &[
+            Symmetry::rotation(Rotation::R90),
+            Symmetry::rotation(Rotation::R270),
+            Symmetry::reflection(Rotation::R90),
+            Symmetry::reflection(Rotation::R270),
+        ]
+    } else {
+        &Symmetry::ALL
+    };
+
+    let mut smallest: Option<(T, Symmetry)> = None;
+    for &s in candidates {
+        let board = p.repr.transform(p.dimension, s);
+        match &smallest {
+            Some((smallest_board, _))
+                if smallest_board
+                    .cmp_rows(&board, s.apply_dim(p.dimension))
+                    .is_le() => {}
+            _ => smallest = Some((board, s)),
+        }
+    }
+    let (repr, s) = smallest.unwrap();
+
+    (
+        Polyomino {
+            square_count: p.square_count,
+            dimension: s.apply_dim(p.dimension),
+            repr,
+            mask: p.mask.transform(p.dimension, s),
+        },
+        s,
+    )
+}
+
+/// Return the representative of the polyomino for the given mode,
+/// and the symmetry mapping the polyomino to it
+pub fn canonical<T>(p: Polyomino<T>, mode: Mode) -> (Polyomino<T>, Symmetry)
+where
+    T: Grid,
+{
+    match mode {
+        // Polyominoes are always kept against the origin, so there is nothing to do
+        Mode::Fixed => (p, Symmetry::IDENTITY),
+        Mode::OneSided => {
+            let (p, r) = smallest_rotation(p);
+            (p, Symmetry::rotation(r))
+        }
+        Mode::Free => smallest_symmetry(p),
+    }
+}
+
+/// Return all symmetries leaving the polyomino invariant
+pub fn stabilizer<T>(p: &Polyomino<T>) -> Vec<Symmetry>
+where
+    T: Grid,
+{
+    Symmetry::ALL
+        .into_iter()
+        .filter(|s| s.apply_dim(p.dimension) == p.dimension)
+        .filter(|&s| p.repr.transform(p.dimension, s) == p.repr)
+        .collect()
+}
+
+pub fn symmetry_class<T>(p: &Polyomino<T>) -> SymmetryClass
+where
+    T: Grid,
+{
+    SymmetryClass::from_stabilizer(&stabilizer(p))
+}
+
 #[cfg(test)]
 mod tests {
     use std::{collections::hash_map::DefaultHasher, hash::Hasher};
 
     use crate::{
+        board::Board,
+        enumerator::Enumerator,
         grid::{are_equal, transfer},
-        grids::naive::Naive,
+        grids::{block_grid::BlockGrid, naive::Naive},
     };
 
     use super::*;
@@ -347,4 +728,120 @@ mod tests {
 
         polyominoes
     }
+
+    fn check_levels<T>()
+    where
+        T: Grid,
+    {
+        let mut level = vec![Polyomino::<T>::trivial()];
+        for _ in 0..4 {
+            level = level
+                .iter()
+                .flat_map(|p| check_decline(p.clone()))
+                .map(|p| smallest_symmetry(p).0)
+                .collect();
+        }
+    }
+
+    #[test]
+    fn decline_block_grid() {
+        check_levels::<BlockGrid>();
+    }
+
+    #[test]
+    fn decline_board() {
+        check_levels::<Board>();
+    }
+
+    #[test]
+    fn from_cells() {
+        let p = Polyomino::<Naive>::from_cells(&[(3, 5), (4, 5), (4, 6)]);
+        assert_eq!(p.square_count, 3);
+        assert_eq!(p.dimension, (4, 4));
+        assert_eq!(p.size(), (2, 2));
+        assert_eq!(p.cells(), vec![(0, 0), (1, 0), (1, 1)]);
+        assert_eq!(p.mask.count(), 7);
+        assert!(p.mask.get(0, 1) && p.mask.get(1, 2) && !p.mask.get(2, 2));
+    }
+
+    #[test]
+    fn holes() {
+        // Two holes in a 6×3 rectangle
+        let mut cells: Vec<_> = (0..6).flat_map(|x| [(x, 0), (x, 2)]).collect();
+        cells.extend([(0, 1), (2, 1), (5, 1)]);
+        let p = Polyomino::<BlockGrid>::from_cells(&cells);
+        assert_eq!(p.holes(), vec![vec![(1, 1)], vec![(3, 1), (4, 1)]]);
+
+        // Cells only touching the outside by a corner are enclosed
+        let p = Polyomino::<Naive>::from_cells(&[
+            (0, 0),
+            (1, 0),
+            (2, 0),
+            (0, 1),
+            (2, 1),
+            (0, 2),
+            (1, 2),
+        ]);
+        assert_eq!(p.holes(), vec![vec![(1, 1)]]);
+
+        let p = Polyomino::<Board>::from_cells(&[(0, 0), (1, 0), (2, 0), (0, 1), (2, 1)]);
+        assert!(!p.has_holes());
+
+        // The bitboard fill agrees with the list of holes
+        for p in Enumerator::<Naive>::range(1..=10).mode(Mode::Fixed) {
+            assert_eq!(p.has_holes(), !p.holes().is_empty(), "{:?}", p.cells());
+        }
+
+        // Wider polyominoes than the bitboards
+        let mut cells: Vec<_> = (0..70).flat_map(|x| [(x, 0), (x, 2)]).collect();
+        cells.push((0, 1));
+        let p = Polyomino::<BlockGrid>::from_cells(&cells);
+        assert!(!p.has_holes());
+        cells.push((69, 1));
+        let p = Polyomino::<BlockGrid>::from_cells(&cells);
+        assert!(p.has_holes());
+    }
+
+    #[test]
+    fn smallest_symmetry_is_invariant() {
+        let mut level = vec![Polyomino::<Naive>::trivial()];
+        for _ in 0..4 {
+            level = level.iter().flat_map(decline).collect();
+        }
+
+        for p in level.into_iter() {
+            let (smallest, s) = smallest_symmetry(p.clone());
+            are_equal(&smallest.repr, &p.repr.transform(p.dimension, s));
+            assert!(smallest.dimension.0 <= smallest.dimension.1);
+
+            for s in Symmetry::ALL {
+                let transformed = Polyomino {
+                    square_count: p.square_count,
+                    dimension: s.apply_dim(p.dimension),
+                    repr: p.repr.transform(p.dimension, s),
+                    mask: p.mask.transform(p.dimension, s),
+                };
+                are_equal(&smallest_symmetry(transformed).0.repr, &smallest.repr);
+            }
+        }
+    }
+
+    #[test]
+    fn same_representatives_for_all_backends() {
+        for p in Enumerator::<Naive>::range(1..=8).mode(Mode::Fixed) {
+            let cells = p.cells();
+            for mode in [Mode::OneSided, Mode::Free] {
+                let (naive, s) = canonical(p.clone(), mode);
+                let (block_grid, block_grid_s) =
+                    canonical(Polyomino::<BlockGrid>::from_cells(&cells), mode);
+                let (board, board_s) = canonical(Polyomino::<Board>::from_cells(&cells), mode);
+                assert_eq!(block_grid.cells(), naive.cells());
+                assert_eq!(board.cells(), naive.cells());
+                // The symmetries may differ for the polyominoes with symmetries
+                if stabilizer(&p).len() == 1 {
+                    assert_eq!((block_grid_s, board_s), (s, s));
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,251 @@
+//! Geometric properties of polyominoes, computed from their squares.
+//! The properties depending on the orientation (the dimension, directedness and the
+//! contacts with the sides) are the ones of the polyomino as stored, so the ones of the
+//! representative when polyominoes are identified up to rotations.
+
+use crate::{domino::count_polyomino_tilings, grid::Grid, polyomino::Polyomino};
+
+/// Number of squares touching each side of the bounding box
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+pub struct Sides {
+    pub left: usize,
+    pub right: usize,
+    pub bottom: usize,
+    pub top: usize,
+}
+
+/// All properties of a polyomino, see the functions of the module
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Properties {
+    pub perimeter: usize,
+    pub site_perimeter: usize,
+    pub width: usize,
+    pub height: usize,
+    pub row_convex: bool,
+    pub column_convex: bool,
+    pub directed: bool,
+    pub tree_like: bool,
+    pub sides: Sides,
+    /// Number of tilings of the polyomino by dominoes
+    pub domino_tilings: u128,
+}
+
+impl Properties {
+    pub fn of<T: Grid>(p: &Polyomino<T>) -> Self {
+        let (width, height) = p.size();
+        Self {
+            perimeter: perimeter(p),
+            site_perimeter: site_perimeter(p),
+            width,
+            height,
+            row_convex: is_row_convex(p),
+            column_convex: is_column_convex(p),
+            directed: is_directed(p),
+            tree_like: is_tree_like(p),
+            sides: sides(p),
+            domino_tilings: count_polyomino_tilings(p),
+        }
+    }
+
+    pub fn convex(&self) -> bool {
+        self.row_convex && self.column_convex
+    }
+}
+
+/// Whether there is a square at (x, y) of the bounding box with the margin,
+/// the coordinates outside of it being empty
+fn square<T: Grid>(p: &Polyomino<T>, x: usize, y: usize) -> bool {
+    x < p.dimension.0 as usize && y < p.dimension.1 as usize && p.repr.get(x, y)
+}
+
+/// The squares, in the coordinates of the bounding box with the margin
+fn squares<T: Grid>(p: &Polyomino<T>) -> impl Iterator<Item = (usize, usize)> + '_ {
+    let (width, height) = (p.dimension.0 as usize, p.dimension.1 as usize);
+    (0..height)
+        .flat_map(move |y| (0..width).map(move |x| (x, y)))
+        .filter(|&(x, y)| p.repr.get(x, y))
+}
+
+/// Number of sides of the squares not shared with another square
+pub fn perimeter<T: Grid>(p: &Polyomino<T>) -> usize {
+    let mut perimeter = 0;
+    for (x, y) in squares(p) {
+        // The margin keeps the neighbours inside of the bounding box
+        for (x, y) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+            if !p.repr.get(x, y) {
+                perimeter += 1;
+            }
+        }
+    }
+    perimeter
+}
+
+/// Number of empty cells sharing a side with a square, where a square can be added,
+/// including the cells of the holes
+pub fn site_perimeter<T: Grid>(p: &Polyomino<T>) -> usize {
+    let (width, height) = (p.dimension.0 as usize, p.dimension.1 as usize);
+    let mut sites = 0;
+    for y in 0..height {
+        for x in 0..width {
+            if p.repr.get(x, y) {
+                continue;
+            }
+            let touches = (x > 0 && square(p, x - 1, y))
+                || square(p, x + 1, y)
+                || (y > 0 && square(p, x, y - 1))
+                || square(p, x, y + 1);
+            if touches {
+                sites += 1;
+            }
+        }
+    }
+    sites
+}
+
+/// Whether the squares are contiguous in each of the lines
+fn lines_are_contiguous<I>(mut lines: impl Iterator<Item = I>) -> bool
+where
+    I: Iterator<Item = bool>,
+{
+    lines.all(|line| {
+        // Count the runs of squares
+        let mut runs = 0;
+        let mut previous = false;
+        for square in line {
+            if square && !previous {
+                runs += 1;
+            }
+            previous = square;
+        }
+        runs <= 1
+    })
+}
+
+/// Whether the squares of each row are contiguous
+pub fn is_row_convex<T: Grid>(p: &Polyomino<T>) -> bool {
+    let (width, height) = (p.dimension.0 as usize, p.dimension.1 as usize);
+    lines_are_contiguous((0..height).map(|y| (0..width).map(move |x| p.repr.get(x, y))))
+}
+
+/// Whether the squares of each column are contiguous
+pub fn is_column_convex<T: Grid>(p: &Polyomino<T>) -> bool {
+    let (width, height) = (p.dimension.0 as usize, p.dimension.1 as usize);
+    lines_are_contiguous((0..width).map(|x| (0..height).map(move |y| p.repr.get(x, y))))
+}
+
+/// Whether the polyomino is both row-convex and column-convex
+pub fn is_convex<T: Grid>(p: &Polyomino<T>) -> bool {
+    is_row_convex(p) && is_column_convex(p)
+}
+
+/// Whether all squares can be reached from a single source square by steps up and
+/// right inside of the polyomino, that is whether a single square has neither a
+/// square on its left nor below it
+pub fn is_directed<T: Grid>(p: &Polyomino<T>) -> bool {
+    let sources = squares(p)
+        .filter(|&(x, y)| !p.repr.get(x - 1, y) && !p.repr.get(x, y - 1))
+        .count();
+    sources == 1
+}
+
+/// Whether no 2×2 block is filled with squares
+pub fn is_tree_like<T: Grid>(p: &Polyomino<T>) -> bool {
+    squares(p)
+        .all(|(x, y)| !(p.repr.get(x + 1, y) && p.repr.get(x, y + 1) && p.repr.get(x + 1, y + 1)))
+}
+
+/// Number of squares touching each side of the bounding box
+pub fn sides<T: Grid>(p: &Polyomino<T>) -> Sides {
+    let (right, top) = (p.dimension.0 as usize - 2, p.dimension.1 as usize - 2);
+    let mut sides = Sides::default();
+    for (x, y) in squares(p) {
+        sides.left += (x == 1) as usize;
+        sides.right += (x == right) as usize;
+        sides.bottom += (y == 1) as usize;
+        sides.top += (y == top) as usize;
+    }
+    sides
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        enumerator::Enumerator,
+        grids::{block_grid::BlockGrid, naive::Naive},
+        polyomino::Mode,
+    };
+
+    use super::*;
+
+    #[test]
+    fn tetrominoes() {
+        let t = Polyomino::<Naive>::from_cells(&[(0, 0), (1, 0), (2, 0), (1, 1)]);
+        assert_eq!(
+            Properties::of(&t),
+            Properties {
+                perimeter: 10,
+                site_perimeter: 8,
+                width: 3,
+                height: 2,
+                row_convex: true,
+                column_convex: true,
+                directed: true,
+                tree_like: true,
+                sides: Sides {
+                    left: 1,
+                    right: 1,
+                    bottom: 3,
+                    top: 1,
+                },
+                domino_tilings: 0,
+            }
+        );
+
+        let o = Polyomino::<Naive>::from_cells(&[(0, 0), (1, 0), (0, 1), (1, 1)]);
+        assert_eq!((perimeter(&o), site_perimeter(&o)), (8, 8));
+        assert!(!is_tree_like(&o));
+        assert_eq!(Properties::of(&o).domino_tilings, 2);
+
+        let z = Polyomino::<Naive>::from_cells(&[(0, 1), (1, 1), (1, 0), (2, 0)]);
+        assert!(is_convex(&z));
+        assert!(!is_directed(&z));
+    }
+
+    #[test]
+    fn holes_and_gaps() {
+        // The U pentomino, and the heptomino with a hole
+        let u = Polyomino::<Naive>::from_cells(&[(0, 0), (1, 0), (2, 0), (0, 1), (2, 1)]);
+        assert!(!is_row_convex(&u) && is_column_convex(&u));
+        assert_eq!(site_perimeter(&u), 10);
+
+        let ring = Polyomino::<Naive>::from_cells(&[
+            (0, 0),
+            (1, 0),
+            (2, 0),
+            (0, 1),
+            (2, 1),
+            (0, 2),
+            (1, 2),
+        ]);
+        assert_eq!(perimeter(&ring), 16);
+        assert_eq!(site_perimeter(&ring), 12);
+    }
+
+    #[test]
+    fn fixed_counts() {
+        let mut convex = vec![];
+        let mut directed = vec![];
+        for n in 1..=8 {
+            let level: Vec<_> = Enumerator::<BlockGrid>::new(n).mode(Mode::Fixed).collect();
+            for p in level.iter().filter(|p| is_convex(p)) {
+                // The perimeter of a convex polyomino is the one of its bounding box
+                let (width, height) = p.size();
+                assert_eq!(perimeter(p), 2 * (width + height));
+            }
+            convex.push(level.iter().filter(|p| is_convex(p)).count());
+            directed.push(level.iter().filter(|p| is_directed(p)).count());
+        }
+        assert_eq!(convex, [1, 2, 6, 19, 59, 176, 502, 1374]);
+        assert_eq!(directed, [1, 2, 5, 13, 35, 96, 267, 750]);
+    }
+}
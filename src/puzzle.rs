@@ -0,0 +1,366 @@
+//! Packing puzzles: placing each piece of a set once inside of a region, such as the
+//! twelve pentominoes in a 6×10 box, or tiling a region with copies of one piece.
+//! Placements are bitboards, and the search always covers the first free cell of the
+//! region, by column then by row. Regions higher than wide are transposed with the
+//! pieces, so that the columns are short.
+
+use crate::{
+    board::{Board, SIZE},
+    grid::Grid,
+    polyomino::{Mode, Polyomino},
+    rotation::Symmetry,
+    tiling::{orientations, LabelledGrid},
+};
+
+/// A packing puzzle. Pieces are told apart by their index, which labels their cells
+/// in the solutions, so swapping two identical pieces gives another solution.
+/// In a tiling, the only piece is placed any number of times, the copies being
+/// labelled in the order of the search
+pub struct Puzzle {
+    /// The region, transposed if `transposed` is set
+    region: Board,
+    transposed: bool,
+    /// Dimension of the bounding box of the region, which starts at the origin
+    dimension: (usize, usize),
+    pieces: usize,
+    /// Whether the pieces can be placed several times
+    copies: bool,
+    /// Number of cells of the region left empty by the pieces,
+    /// none if the pieces cannot fill the region
+    empty: Option<usize>,
+    /// The placements of each piece, indexed by their first cell
+    placements: Vec<Vec<(usize, Vec<Board>)>>,
+    /// The symmetries of the region which map solutions to solutions
+    symmetries: Vec<Symmetry>,
+}
+
+impl Puzzle {
+    /// Place the pieces in the elements of the grid set to true, which must fit a
+    /// [`SIZE`] × [`SIZE`] box. With [`Mode::Fixed`], pieces are placed as given, with
+    /// [`Mode::OneSided`] they may be turned, and with [`Mode::Free`] also reflected
+    pub fn new<T: Grid, U: Grid>(pieces: &[Polyomino<T>], region: &U, mode: Mode) -> Self {
+        let area: usize = pieces.iter().map(|p| p.square_count as usize).sum();
+        let mut puzzle = Self::placing(pieces, region, mode);
+        puzzle.empty = (puzzle.region.count() as usize).checked_sub(area);
+        puzzle
+    }
+
+    /// Cover the elements of the grid set to true with copies of the piece, like
+    /// [`Puzzle::new`]
+    pub fn tiling<T: Grid, U: Grid>(piece: &Polyomino<T>, region: &U, mode: Mode) -> Self {
+        let mut puzzle = Self::placing(std::slice::from_ref(piece), region, mode);
+        puzzle.copies = true;
+        let squares = piece.square_count as usize;
+        puzzle.empty = (puzzle.region.count() as usize)
+            .is_multiple_of(squares)
+            .then_some(0);
+        puzzle
+    }
+
+    /// The puzzle with the placements of the pieces, leaving no cell empty
+    fn placing<T: Grid, U: Grid>(pieces: &[Polyomino<T>], region: &U, mode: Mode) -> Self {
+        let cells: Vec<(usize, usize)> = {
+            let (width, height) = region.get_bounding_box();
+            (0..width)
+                .flat_map(|x| (0..height).map(move |y| (x, y)))
+                .filter(|&(x, y)| region.get(x, y))
+                .collect()
+        };
+        let min_x = cells.iter().map(|&(x, _)| x).min().unwrap_or(0);
+        let min_y = cells.iter().map(|&(_, y)| y).min().unwrap_or(0);
+        let width = cells.iter().map(|&(x, _)| x + 1 - min_x).max().unwrap_or(0);
+        let height = cells.iter().map(|&(_, y)| y + 1 - min_y).max().unwrap_or(0);
+        assert!(
+            width <= SIZE && height <= SIZE,
+            "The region does not fit a board"
+        );
+        let transposed = height > width;
+        let transpose = |(x, y): (usize, usize)| if transposed { (y, x) } else { (x, y) };
+
+        let mut original = Board::new();
+        let mut board = Board::new();
+        for &(x, y) in cells.iter() {
+            original.set(x - min_x, y - min_y);
+            let (x, y) = transpose((x - min_x, y - min_y));
+            board.set(x, y);
+        }
+        let (columns, rows) = transpose((width, height));
+
+        let mut placements = vec![vec![]; SIZE * SIZE];
+        for (piece, p) in pieces.iter().enumerate() {
+            let orientations = match mode {
+                Mode::Fixed => vec![p.cells()],
+                Mode::OneSided => orientations(p, false),
+                Mode::Free => orientations(p, true),
+            };
+            for cells in orientations.iter() {
+                for dx in 0..columns {
+                    for dy in 0..rows {
+                        let mut placement = Board::new();
+                        let mut inside = true;
+                        for &cell in cells.iter() {
+                            let (x, y) = transpose(cell);
+                            let (x, y) = (x + dx, y + dy);
+                            if x >= columns || y >= rows || !board.get(x, y) {
+                                inside = false;
+                                break;
+                            }
+                            placement.set(x, y);
+                        }
+                        if let (true, Some((x, y))) = (inside, placement.first()) {
+                            let at: &mut Vec<(usize, Vec<Board>)> = &mut placements[x * SIZE + y];
+                            match at.last_mut() {
+                                Some((last, boards)) if *last == piece => boards.push(placement),
+                                _ => at.push((piece, vec![placement])),
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let dim = (width as u8, height as u8);
+        let symmetries = Symmetry::ALL
+            .into_iter()
+            .filter(|s| mode == Mode::Free || (mode == Mode::OneSided && !s.mirror))
+            .filter(|&s| s.apply_dim(dim) == dim && original.transform(dim, s) == original)
+            .collect();
+
+        Self {
+            region: board,
+            transposed,
+            dimension: (width, height),
+            pieces: pieces.len(),
+            copies: false,
+            empty: Some(0),
+            placements,
+            symmetries,
+        }
+    }
+
+    /// Call `visit` with each solution, until it returns false.
+    /// Return false if the search was stopped
+    pub fn solve<F>(&self, mut visit: F) -> bool
+    where
+        F: FnMut(&LabelledGrid) -> bool,
+    {
+        let Some(empty) = self.empty else {
+            return true;
+        };
+        let mut placed = vec![];
+        let mut used = vec![false; self.pieces];
+        self.search(self.region, empty, &mut used, &mut placed, &mut visit)
+    }
+
+    fn search<F>(
+        &self,
+        mut free: Board,
+        empty: usize,
+        used: &mut [bool],
+        placed: &mut Vec<(usize, Board)>,
+        visit: &mut F,
+    ) -> bool
+    where
+        F: FnMut(&LabelledGrid) -> bool,
+    {
+        let Some((x, y)) = free.first() else {
+            return visit(&self.labels(placed));
+        };
+
+        for (piece, boards) in self.placements[x * SIZE + y].iter() {
+            if used[*piece] && !self.copies {
+                continue;
+            }
+            for placement in boards.iter().filter(|&placement| free.contains(placement)) {
+                let mut next = free;
+                next.sub(placement);
+                used[*piece] = true;
+                placed.push((*piece, *placement));
+                let running = self.search(next, empty, used, placed, visit);
+                placed.pop();
+                used[*piece] = false;
+                if !running {
+                    return false;
+                }
+            }
+        }
+
+        // Leave the cell empty, if the pieces still fit the rest of the region
+        if empty > 0 {
+            free.unset(x, y);
+            return self.search(free, empty - 1, used, placed, visit);
+        }
+        true
+    }
+
+    fn labels(&self, placed: &[(usize, Board)]) -> LabelledGrid {
+        let mut grid = LabelledGrid::new(self.dimension.0, self.dimension.1);
+        for (copy, (piece, placement)) in placed.iter().enumerate() {
+            let label = if self.copies { copy } else { *piece };
+            for x in 0..self.dimension.0 {
+                for y in 0..self.dimension.1 {
+                    let (column, row) = if self.transposed { (y, x) } else { (x, y) };
+                    if placement.get(column, row) {
+                        grid.set(x, y, label);
+                    }
+                }
+            }
+        }
+        grid
+    }
+
+    /// Whether the solution is the smallest of its images by the symmetries of the region.
+    /// The copies of a tiling are relabelled first, so that only their shapes count
+    fn is_canonical(&self, solution: &LabelledGrid) -> bool {
+        let (width, height) = self.dimension;
+        let dim = (width as u8, height as u8);
+        let labels = (0..width)
+            .flat_map(|x| (0..height).filter_map(move |y| solution.get(x, y)))
+            .max()
+            .map_or(0, |label| label + 1);
+        let relabel = |grid: LabelledGrid| if self.copies { grid.relabelled() } else { grid };
+        let solution = relabel(solution.clone());
+        self.symmetries.iter().all(|&s| {
+            let mut image = LabelledGrid::new(width, height);
+            for label in 0..labels {
+                let mut cells = Board::new();
+                for x in 0..width {
+                    for y in 0..height {
+                        if solution.get(x, y) == Some(label) {
+                            cells.set(x, y);
+                        }
+                    }
+                }
+                let cells = cells.transform(dim, s);
+                for x in 0..width {
+                    for y in 0..height {
+                        if cells.get(x, y) {
+                            image.set(x, y, label);
+                        }
+                    }
+                }
+            }
+            solution <= relabel(image)
+        })
+    }
+
+    /// Return a solution, if there is one
+    pub fn first(&self) -> Option<LabelledGrid> {
+        let mut first = None;
+        self.solve(|solution| {
+            first = Some(solution.clone());
+            false
+        });
+        first
+    }
+
+    /// Return the number of solutions
+    pub fn count(&self) -> u128 {
+        let mut count = 0;
+        self.solve(|_| {
+            count += 1;
+            true
+        });
+        count
+    }
+
+    /// Return one solution of each class of solutions mapped to each other by
+    /// the symmetries of the region, when they map the pieces to allowed placements
+    pub fn distinct_solutions(&self) -> Vec<LabelledGrid> {
+        let mut solutions = vec![];
+        self.solve(|solution| {
+            if self.is_canonical(solution) {
+                solutions.push(solution.clone());
+            }
+            true
+        });
+        solutions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        enumerator::Enumerator,
+        grids::{block_grid::BlockGrid, naive::Naive, tests::rectangle},
+    };
+
+    use super::*;
+
+    #[test]
+    fn pentominoes_in_3x20() {
+        let pentominoes: Vec<_> = Enumerator::<BlockGrid>::new(5).mode(Mode::Free).collect();
+        assert_eq!(pentominoes.len(), 12);
+
+        let puzzle = Puzzle::new(&pentominoes, &rectangle(3, 20), Mode::Free);
+        assert_eq!(puzzle.count(), 8);
+        let solutions = puzzle.distinct_solutions();
+        assert_eq!(solutions.len(), 2);
+
+        // Each pentomino is used once
+        for solution in solutions.iter() {
+            let mut sizes = [0; 12];
+            for x in 0..3 {
+                for y in 0..20 {
+                    sizes[solution.get(x, y).unwrap()] += 1;
+                }
+            }
+            assert_eq!(sizes, [5; 12]);
+        }
+    }
+
+    #[test]
+    fn one_sided_pieces() {
+        // Two L trominoes tile the 2×3 rectangle in two ways, with two labellings
+        // each, which the half turn of the rectangle swaps
+        let l = Polyomino::<Naive>::from_cells(&[(0, 0), (1, 0), (0, 1)]);
+        let puzzle = Puzzle::new(&vec![l.clone(); 2], &rectangle(2, 3), Mode::OneSided);
+        assert_eq!(puzzle.count(), 4);
+        assert_eq!(puzzle.distinct_solutions().len(), 2);
+
+        // Fixed pieces cannot be turned to fill the rectangle
+        let puzzle = Puzzle::new(&vec![l.clone(); 2], &rectangle(2, 3), Mode::Fixed);
+        assert_eq!(puzzle.count(), 0);
+    }
+
+    #[test]
+    fn region_with_holes() {
+        // The 3×3 ring is cut in two L tetrominoes in four ways, with two labellings
+        // each, all mapped to each other by the symmetries of the ring
+        let mut ring = rectangle(3, 3);
+        ring.unset(1, 1);
+        let l = Polyomino::<Naive>::from_cells(&[(0, 0), (1, 0), (2, 0), (0, 1)]);
+        let puzzle = Puzzle::new(&vec![l; 2], &ring, Mode::Free);
+        assert_eq!(puzzle.count(), 8);
+        assert_eq!(puzzle.distinct_solutions().len(), 1);
+        let solution = puzzle.first().unwrap();
+        assert_eq!(solution.get(1, 1), None);
+
+        // A larger region leaves cells empty
+        let domino = Polyomino::<Naive>::from_cells(&[(0, 0), (1, 0)]);
+        let puzzle = Puzzle::new(
+            std::slice::from_ref(&domino),
+            &rectangle(2, 2),
+            Mode::OneSided,
+        );
+        assert_eq!(puzzle.count(), 4);
+        assert_eq!(puzzle.distinct_solutions().len(), 1);
+        let puzzle = Puzzle::new(&vec![domino; 3], &rectangle(2, 2), Mode::Free);
+        assert_eq!(puzzle.count(), 0);
+    }
+
+    #[test]
+    fn tilings() {
+        // Three dominoes tile the 2×3 rectangle in three ways, labelled by copy, the
+        // two with a vertical pair being mirror images
+        let domino = Polyomino::<Naive>::from_cells(&[(0, 0), (1, 0)]);
+        let puzzle = Puzzle::tiling(&domino, &rectangle(2, 3), Mode::OneSided);
+        assert_eq!(puzzle.count(), 3);
+        assert_eq!(puzzle.distinct_solutions().len(), 2);
+        let solution = puzzle.first().unwrap();
+        assert!((0..2).all(|x| (0..3).all(|y| solution.get(x, y).is_some_and(|l| l < 3))));
+
+        let puzzle = Puzzle::tiling(&domino, &rectangle(3, 3), Mode::Free);
+        assert_eq!(puzzle.count(), 0);
+    }
+}
@@ -0,0 +1,111 @@
+//! Redelmeier's algorithm, counting fixed polyominoes depth first
+//! without storing them, in memory linear in their size.
+//! Polyominoes are grown from the origin, which is their lowest, then leftmost square,
+//! so cells below it, or left of it on its row, are forbidden.
+
+/// Counts fixed polyominoes with up to `max` squares.
+/// Cells are indexed by rows of `width` cells, with a margin on each side,
+/// starting with the forbidden row below the origin
+pub struct Redelmeier {
+    max: usize,
+    width: usize,
+    /// Cells which are occupied, neighbours of the polyomino, or forbidden
+    seen: Vec<bool>,
+    /// The untried set of each depth is a contiguous slice of this stack
+    untried: Vec<usize>,
+    /// As wide as the counts of the other engines, which they are compared with
+    counts: Vec<u128>,
+}
+
+impl Redelmeier {
+    pub fn new(max: usize) -> Self {
+        assert!(max > 0, "There are no polyominoes with zero square");
+
+        // Squares are at most max - 1 cells away from the origin
+        let width = 2 * max + 1;
+        let mut seen = vec![false; width * (max + 1)];
+        // The row below the origin, and the cells left of the origin on its row
+        seen[..width + max].fill(true);
+
+        Self {
+            max,
+            width,
+            seen,
+            untried: Vec::with_capacity(4 * max),
+            counts: vec![0; max],
+        }
+    }
+
+    fn origin(&self) -> usize {
+        self.width + self.max
+    }
+
+    /// Return the number of fixed polyominoes with 1 to `max` squares
+    pub fn count(mut self) -> Vec<u128> {
+        let origin = self.origin();
+        self.seen[origin] = true;
+        self.untried.push(origin);
+        self.grow(0, 0);
+        self.counts
+    }
+
+    /// Add each cell of the untried set from `start`, in turn, to a polyomino of `depth` squares
+    fn grow(&mut self, start: usize, depth: usize) {
+        let end = self.untried.len();
+        for i in start..end {
+            self.counts[depth] += 1;
+            if depth + 1 == self.max {
+                continue;
+            }
+
+            // The new neighbours are untried for the children only
+            let cell = self.untried[i];
+            for neighbour in [cell - self.width, cell - 1, cell + 1, cell + self.width] {
+                if !self.seen[neighbour] {
+                    self.seen[neighbour] = true;
+                    self.untried.push(neighbour);
+                }
+            }
+
+            self.grow(i + 1, depth + 1);
+
+            for &neighbour in self.untried[end..].iter() {
+                self.seen[neighbour] = false;
+            }
+            self.untried.truncate(end);
+        }
+    }
+}
+
+/// Count fixed polyominoes with 1 to `max` squares
+pub fn count_fixed(max: usize) -> Vec<u128> {
+    Redelmeier::new(max).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{database::Database, grids::block_grid::BlockGrid, polyomino::Mode};
+
+    use super::*;
+
+    #[test]
+    fn same_counts_as_database() {
+        let mut db = Database::<BlockGrid>::with_mode(Mode::Fixed);
+        while db.counts().len() < 9 {
+            match db.pop() {
+                None => db.flush(),
+                Some(p) => db.expand(&p),
+            }
+        }
+        assert!(count_fixed(9).iter().eq(db.counts()));
+    }
+
+    #[test]
+    fn known_counts() {
+        assert_eq!(count_fixed(1), [1]);
+        assert_eq!(
+            count_fixed(12),
+            [1, 2, 6, 19, 63, 216, 760, 2725, 9910, 36446, 135268, 505861]
+        );
+    }
+}
@@ -1,8 +1,140 @@
 /// Trigonometric rotation in degrees
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum Rotation {
     R0,
     R90,
     R180,
     R270,
 }
+
+impl Rotation {
+    pub const ALL: [Rotation; 4] = [Rotation::R0, Rotation::R90, Rotation::R180, Rotation::R270];
+}
+
+/// An element of the dihedral group D4, the eight symmetries of the square.
+/// The grid is first mirrored along the X axis (x -> dim.0 - 1 - x) if
+/// `mirror` is set, then rotated by `rotation`
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Symmetry {
+    pub mirror: bool,
+    pub rotation: Rotation,
+}
+
+impl Symmetry {
+    pub const IDENTITY: Symmetry = Symmetry::rotation(Rotation::R0);
+
+    pub const ALL: [Symmetry; 8] = [
+        Symmetry::rotation(Rotation::R0),
+        Symmetry::rotation(Rotation::R90),
+        Symmetry::rotation(Rotation::R180),
+        Symmetry::rotation(Rotation::R270),
+        Symmetry::reflection(Rotation::R0),
+        Symmetry::reflection(Rotation::R90),
+        Symmetry::reflection(Rotation::R180),
+        Symmetry::reflection(Rotation::R270),
+    ];
+
+    /// A rotation, without mirroring
+    pub const fn rotation(rotation: Rotation) -> Self {
+        Self {
+            mirror: false,
+            rotation,
+        }
+    }
+
+    /// A mirror along the X axis followed by a rotation
+    pub const fn reflection(rotation: Rotation) -> Self {
+        Self {
+            mirror: true,
+            rotation,
+        }
+    }
+
+    /// Return the dimension of a bounding box of dimension `dim` once transformed
+    pub fn apply_dim(&self, dim: (u8, u8)) -> (u8, u8) {
+        match self.rotation {
+            Rotation::R0 | Rotation::R180 => dim,
+            Rotation::R90 | Rotation::R270 => (dim.1, dim.0),
+        }
+    }
+}
+
+/// The subgroups of D4 a polyomino can be invariant under, up to conjugation.
+/// Mirror axes are either parallel to the grid lines (mirror90) or diagonal (mirror45)
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum SymmetryClass {
+    /// Only invariant under the identity
+    Asymmetric,
+    /// A single mirror axis parallel to the grid lines
+    Mirror90,
+    /// A single diagonal mirror axis
+    Mirror45,
+    /// Invariant under a half turn
+    Rot2,
+    /// Invariant under a half turn and two mirror axes parallel to the grid lines
+    Rot2Mirror90,
+    /// Invariant under a half turn and two diagonal mirror axes
+    Rot2Mirror45,
+    /// Invariant under a quarter turn
+    Rot4,
+    /// Invariant under all symmetries of the square
+    All,
+}
+
+impl SymmetryClass {
+    pub const ALL: [SymmetryClass; 8] = [
+        SymmetryClass::Asymmetric,
+        SymmetryClass::Mirror90,
+        SymmetryClass::Mirror45,
+        SymmetryClass::Rot2,
+        SymmetryClass::Rot2Mirror90,
+        SymmetryClass::Rot2Mirror45,
+        SymmetryClass::Rot4,
+        SymmetryClass::All,
+    ];
+
+    /// Classify a stabilizer, given as the list of symmetries leaving a shape invariant
+    pub fn from_stabilizer(stabilizer: &[Symmetry]) -> Self {
+        let has = |s: Symmetry| stabilizer.contains(&s);
+        let rot2 = has(Symmetry::rotation(Rotation::R180));
+        let rot4 = has(Symmetry::rotation(Rotation::R90));
+        let mirror90 =
+            has(Symmetry::reflection(Rotation::R0)) || has(Symmetry::reflection(Rotation::R180));
+        let mirror45 =
+            has(Symmetry::reflection(Rotation::R90)) || has(Symmetry::reflection(Rotation::R270));
+
+        match (rot4, rot2, mirror90, mirror45) {
+            (true, _, true, _) | (true, _, _, true) => SymmetryClass::All,
+            (true, _, _, _) => SymmetryClass::Rot4,
+            (false, true, true, _) => SymmetryClass::Rot2Mirror90,
+            (false, true, _, true) => SymmetryClass::Rot2Mirror45,
+            (false, true, false, false) => SymmetryClass::Rot2,
+            (false, false, true, _) => SymmetryClass::Mirror90,
+            (false, false, _, true) => SymmetryClass::Mirror45,
+            (false, false, false, false) => SymmetryClass::Asymmetric,
+        }
+    }
+
+    /// The order of the stabilizer
+    pub fn order(&self) -> usize {
+        match self {
+            SymmetryClass::Asymmetric => 1,
+            SymmetryClass::Mirror90 | SymmetryClass::Mirror45 | SymmetryClass::Rot2 => 2,
+            SymmetryClass::Rot2Mirror90 | SymmetryClass::Rot2Mirror45 | SymmetryClass::Rot4 => 4,
+            SymmetryClass::All => 8,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SymmetryClass::Asymmetric => "none",
+            SymmetryClass::Mirror90 => "mirror90",
+            SymmetryClass::Mirror45 => "mirror45",
+            SymmetryClass::Rot2 => "rot2",
+            SymmetryClass::Rot2Mirror90 => "rot2 mirror90",
+            SymmetryClass::Rot2Mirror45 => "rot2 mirror45",
+            SymmetryClass::Rot4 => "rot4",
+            SymmetryClass::All => "all",
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! Tilings of rectangles by copies of a polyomino, solved as [`Puzzle`]s whose only
+//! piece can be placed any number of times.
+
+use std::fmt::{self, Display};
+
+use crate::{
+    board::{Board, SIZE},
+    grid::Grid,
+    polyomino::{Mode, Polyomino},
+    puzzle::Puzzle,
+    rotation::Symmetry,
+};
+
+/// A rectangle of cells, each with the label of the piece covering it, if any
+/// Grids are ordered by their labels, row by row from the bottom
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct LabelledGrid {
+    pub width: usize,
+    pub height: usize,
+    labels: Vec<Option<usize>>,
+}
+
+impl LabelledGrid {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            labels: vec![None; width * height],
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<usize> {
+        self.labels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, label: usize) {
+        self.labels[y * self.width + x] = Some(label);
+    }
+
+    /// Return the grid with the labels renumbered in the order of their first cell,
+    /// row by row from the bottom
+    pub fn relabelled(&self) -> Self {
+        let mut order = vec![];
+        for label in self.labels.iter().flatten() {
+            if !order.contains(label) {
+                order.push(*label);
+            }
+        }
+        let labels = self
+            .labels
+            .iter()
+            .map(|label| label.map(|label| order.iter().position(|&l| l == label).unwrap()))
+            .collect();
+        Self { labels, ..*self }
+    }
+}
+
+/// Render the labels as rows of letters from top to bottom, '.' for the empty cells
+impl Display for LabelledGrid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const LETTERS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+        for y in (0..self.height).rev() {
+            for x in 0..self.width {
+                let c = match self.get(x, y) {
+                    Some(label) => LETTERS[label % LETTERS.len()] as char,
+                    None => '.',
+                };
+                write!(f, "{c}")?;
+            }
+            if y != 0 {
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Return the cells of each distinct orientation of the polyomino, by the rotations,
+/// and the reflections if allowed. Coordinates are relative to the bounding box of the
+/// squares, like [`Polyomino::cells`]
+pub fn orientations<T: Grid>(p: &Polyomino<T>, reflections: bool) -> Vec<Vec<(usize, usize)>> {
+    let mut orientations: Vec<Vec<(usize, usize)>> = vec![];
+    for s in Symmetry::ALL
+        .into_iter()
+        .filter(|s| reflections || !s.mirror)
+    {
+        let turned = Polyomino::from(
+            s.apply_dim(p.dimension),
+            p.repr.transform(p.dimension, s),
+            p.mask.transform(p.dimension, s),
+        );
+        let cells = turned.cells();
+        if !orientations.contains(&cells) {
+            orientations.push(cells);
+        }
+    }
+    orientations
+}
+
+/// Return a tiling of the `width` × `height` rectangle by copies of the polyomino,
+/// turned and, if allowed, reflected, labelled by copy, if there is one. The rectangle
+/// must fit a [`SIZE`] × [`SIZE`] board
+pub fn tile_rectangle<T: Grid>(
+    p: &Polyomino<T>,
+    width: usize,
+    height: usize,
+    reflections: bool,
+) -> Option<LabelledGrid> {
+    assert!(
+        width <= SIZE && height <= SIZE,
+        "The rectangle does not fit a board"
+    );
+    let mut region = Board::new();
+    for x in 0..width {
+        for y in 0..height {
+            region.set(x, y);
+        }
+    }
+    let mode = if reflections {
+        Mode::Free
+    } else {
+        Mode::OneSided
+    };
+    Puzzle::tiling(p, &region, mode).first()
+}
+
+/// Return the dimension of the smallest rectangle by area tiled by copies of the
+/// polyomino, with at most as many columns as rows, among the rectangles of at most
+/// `max_area` cells fitting a board. The number of copies is the order of the polyomino
+pub fn order<T: Grid>(
+    p: &Polyomino<T>,
+    reflections: bool,
+    max_area: usize,
+) -> Option<(usize, usize)> {
+    let squares = p.square_count as usize;
+    (squares..=max_area).step_by(squares).find_map(|area| {
+        (1..=area)
+            .take_while(|width| width * width <= area)
+            .filter(|&width| area.is_multiple_of(width))
+            .map(|width| (width, area / width))
+            .filter(|&(_, height)| height <= SIZE)
+            .find(|&(width, height)| tile_rectangle(p, width, height, reflections).is_some())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grids::{block_grid::BlockGrid, naive::Naive, tests::rectangle};
+
+    use super::*;
+
+    fn pentomino(cells: &[(usize, usize)]) -> Polyomino<BlockGrid> {
+        Polyomino::from_cells(cells)
+    }
+
+    #[test]
+    fn tile_with_l_tetrominoes() {
+        let l = Polyomino::<Naive>::from_cells(&[(0, 0), (1, 0), (0, 1), (0, 2)]);
+        assert_eq!(orientations(&l, false).len(), 4);
+        assert_eq!(orientations(&l, true).len(), 8);
+
+        let tiling = tile_rectangle(&l, 2, 4, false).unwrap();
+        let mut sizes = [0; 2];
+        for y in 0..4 {
+            for x in 0..2 {
+                sizes[tiling.get(x, y).unwrap()] += 1;
+            }
+        }
+        assert_eq!(sizes, [4, 4]);
+        assert_eq!(tiling.to_string().lines().count(), 4);
+
+        assert!(tile_rectangle(&l, 3, 4, false).is_none());
+    }
+
+    #[test]
+    fn domino_tilings() {
+        let domino = Polyomino::<Naive>::from_cells(&[(0, 0), (1, 0)]);
+        let count = |width, height| {
+            Puzzle::tiling(&domino, &rectangle(width, height), Mode::OneSided).count()
+        };
+        // Fibonacci numbers
+        assert_eq!(
+            (1..=7).map(|n| count(2, n)).collect::<Vec<_>>(),
+            [1, 2, 3, 5, 8, 13, 21]
+        );
+        assert_eq!(count(6, 6), 6728);
+    }
+
+    #[test]
+    fn orders() {
+        let t = Polyomino::<Naive>::from_cells(&[(0, 0), (1, 0), (2, 0), (1, 1)]);
+        assert_eq!(order(&t, true, 16), Some((4, 4)));
+        let s = Polyomino::<Naive>::from_cells(&[(0, 0), (1, 0), (1, 1), (2, 1)]);
+        assert_eq!(order(&s, true, 64), None);
+
+        let p = pentomino(&[(0, 0), (1, 0), (0, 1), (1, 1), (0, 2)]);
+        assert_eq!(order(&p, true, 100), Some((2, 5)));
+        let y = pentomino(&[(0, 0), (0, 1), (1, 1), (0, 2), (0, 3)]);
+        assert_eq!(order(&y, true, 100), Some((5, 10)));
+    }
+
+    #[test]
+    fn reflections_matter() {
+        // The L tetromino only tiles a 3×8 rectangle with its mirror image
+        let l = Polyomino::<Naive>::from_cells(&[(0, 0), (1, 0), (0, 1), (0, 2)]);
+        assert!(tile_rectangle(&l, 3, 8, true).is_some());
+        assert!(tile_rectangle(&l, 3, 8, false).is_none());
+    }
+}
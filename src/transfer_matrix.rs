@@ -0,0 +1,348 @@
+//! Jensen's transfer-matrix algorithm, counting fixed polyominoes without enumerating them.
+//!
+//! Polyominoes spanning a strip of `width` rows are built column by column, cell by cell.
+//! The cut between the cells already decided and the others crosses `width` cells: in the
+//! current column above the current row, in the previous column from the current row.
+//! A state gives the component of each occupied cell of the cut, and whether the top and
+//! the bottom rows of the strip have been reached. Each state carries the number of partial
+//! polyominoes leading to it, by number of squares.
+//! A polyomino with a bounding box of h rows and w columns is the transposition of one with
+//! w rows and h columns, so only strips with at most as many rows as columns are swept.
+
+use std::{
+    cmp::Ordering,
+    collections::{hash_map::Entry, HashMap},
+    thread,
+};
+
+/// Labels of the components of the cells of the cut, 4 bits each, 0 for an empty cell,
+/// numbered in order of appearance from the top. The two highest bits are the flags
+type State = u128;
+
+/// The series of the partial polyominoes, by state
+type States = HashMap<State, Series>;
+
+const BITS: usize = 4;
+const TOP: State = 1 << 126;
+const BOTTOM: State = 1 << 127;
+
+/// The widest strip such that the labels of its components fit in a `State`
+pub const MAX_WIDTH: usize = 28;
+
+fn get(state: State, i: usize) -> u8 {
+    ((state >> (BITS * i)) & 0xf) as u8
+}
+
+fn set(state: State, i: usize, label: u8) -> State {
+    state & !(0xf << (BITS * i)) | (label as State) << (BITS * i)
+}
+
+/// Number the components in order of appearance
+fn normalize(state: State, width: usize) -> State {
+    let mut labels = [0u8; 16];
+    let mut next = 0;
+    let mut normalized = state & (TOP | BOTTOM);
+    for i in 0..width {
+        let label = get(state, i) as usize;
+        if label != 0 {
+            if labels[label] == 0 {
+                next += 1;
+                labels[label] = next;
+            }
+            normalized = set(normalized, i, labels[label]);
+        }
+    }
+    normalized
+}
+
+/// Number of partial polyominoes by number of squares, from `first` squares
+#[derive(Clone, Default)]
+struct Series {
+    first: usize,
+    counts: Vec<u128>,
+}
+
+impl Series {
+    /// Add the series of polyominoes with `shift` more squares, without the ones above `max`
+    fn add(&mut self, other: &Series, shift: usize, max: usize) {
+        let first = other.first + shift;
+        let last = (first + other.counts.len()).min(max + 1);
+        if last <= first {
+            return;
+        }
+
+        if self.counts.is_empty() {
+            self.first = first;
+        } else if first < self.first {
+            let mut counts = vec![0; self.first - first];
+            counts.append(&mut self.counts);
+            self.counts = counts;
+            self.first = first;
+        }
+        if self.counts.len() < last - self.first {
+            self.counts.resize(last - self.first, 0);
+        }
+        for (size, count) in (first..last).zip(other.counts.iter()) {
+            self.counts[size - self.first] += count;
+        }
+    }
+}
+
+/// The polyominoes to count in a strip
+struct Strip {
+    width: usize,
+    /// Maximum number of squares
+    max: usize,
+    /// Minimum number of columns
+    min_columns: usize,
+}
+
+impl Strip {
+    /// A lower bound of the number of squares to add to a normalized state, after a cell
+    /// of `column`, to complete a polyomino.
+    /// Components can only be joined through squares in all rows between two of their cells,
+    /// so the rows between consecutive cells of the cut, for a set of such pairs connecting
+    /// all components, must be filled. So must the rows between the cut and the edges of
+    /// the strip not reached yet, which are distinct, and the columns up to the minimum
+    fn needed(&self, state: State, column: usize) -> usize {
+        // Gaps between consecutive cells of different components
+        let mut gaps = [(0, 0, 0); MAX_WIDTH];
+        let mut gap_count = 0;
+        let mut previous: Option<(usize, u8)> = None;
+        let mut top = None;
+        let mut components = 0;
+        for i in 0..self.width {
+            let label = get(state, i);
+            if label == 0 {
+                continue;
+            }
+            top.get_or_insert(i);
+            components = components.max(label as usize);
+            if let Some((row, previous)) = previous.filter(|&(_, l)| l != label) {
+                gaps[gap_count] = (i - row - 1, previous, label);
+                gap_count += 1;
+            }
+            previous = Some((i, label));
+        }
+        let (Some(top), Some((bottom, _))) = (top, previous) else {
+            return 0;
+        };
+
+        // Kruskal's algorithm for the cheapest connecting set of gaps
+        let gaps = &mut gaps[..gap_count];
+        gaps.sort_unstable();
+        let mut parents: [u8; 16] = std::array::from_fn(|i| i as u8);
+        fn root(parents: &mut [u8; 16], mut label: u8) -> u8 {
+            while parents[label as usize] != label {
+                label = parents[label as usize];
+            }
+            label
+        }
+        let mut connect = 0;
+        for &(gap, a, b) in gaps.iter() {
+            let (a, b) = (root(&mut parents, a), root(&mut parents, b));
+            if a != b {
+                parents[a as usize] = b;
+                connect += gap;
+            }
+        }
+
+        let mut edges = 0;
+        if state & TOP == 0 {
+            edges += top;
+        }
+        if state & BOTTOM == 0 {
+            edges += self.width - 1 - bottom;
+        }
+        let columns = (self.min_columns - 1).saturating_sub(column);
+        (connect + edges).max(components - 1).max(columns)
+    }
+
+    /// Add the series, with `shift` more squares, to the state of `next` once normalized,
+    /// if some polyominoes can still be completed
+    fn insert(
+        &self,
+        next: &mut States,
+        state: State,
+        mut series: Series,
+        shift: usize,
+        column: usize,
+    ) {
+        let state = normalize(state, self.width);
+        let Some(max) = self.max.checked_sub(self.needed(state, column)) else {
+            return;
+        };
+        series.first += shift;
+        if series.first > max {
+            return;
+        }
+        series.counts.truncate(max + 1 - series.first);
+
+        match next.entry(state) {
+            Entry::Vacant(entry) => {
+                entry.insert(series);
+            }
+            Entry::Occupied(entry) => entry.into_mut().add(&series, 0, max),
+        }
+    }
+
+    /// Sweep the strip, returning the number of fixed polyominoes spanning it,
+    /// indexed by number of columns - 1, then by number of squares
+    fn sweep(&self) -> Vec<Vec<u128>> {
+        let width = self.width;
+        assert!(
+            (1..=MAX_WIDTH).contains(&width),
+            "The width of the strip must be between 1 and {MAX_WIDTH}"
+        );
+
+        let mut by_length = vec![];
+        let mut states = States::default();
+        states.insert(
+            0,
+            Series {
+                first: 0,
+                counts: vec![1],
+            },
+        );
+
+        let mut column = 0;
+        while !states.is_empty() {
+            for row in 0..width {
+                let mut next = States::with_capacity(states.len());
+                for (state, series) in states.drain() {
+                    let up = if row > 0 { get(state, row - 1) } else { 0 };
+                    let left = get(state, row);
+
+                    // An empty cell, unless it disconnects the component on its left
+                    if left == 0 || (0..width).any(|i| i != row && get(state, i) == left) {
+                        self.insert(&mut next, set(state, row, 0), series.clone(), 0, column);
+                    }
+
+                    // An occupied cell, merging its neighbours
+                    let mut occupied = match (up, left) {
+                        (0, 0) => set(state, row, 0xf),
+                        (label, 0) | (0, label) => set(state, row, label),
+                        (up, left) => (0..width)
+                            .filter(|&i| get(state, i) == left)
+                            .fold(state, |s, i| set(s, i, up)),
+                    };
+                    if row == 0 {
+                        occupied |= TOP;
+                    }
+                    if row == width - 1 {
+                        occupied |= BOTTOM;
+                    }
+                    self.insert(&mut next, occupied, series, 1, column);
+                }
+                states = next;
+            }
+
+            // Polyominoes not started in the first column are translations of others
+            states.remove(&0);
+
+            let mut done = Series::default();
+            for (&state, series) in states.iter() {
+                let single = (0..width).all(|i| get(state, i) <= 1);
+                if state & TOP != 0 && state & BOTTOM != 0 && single {
+                    done.add(series, 0, self.max);
+                }
+            }
+            let mut counts = vec![0; self.max + 1];
+            for (i, &count) in done.counts.iter().enumerate() {
+                counts[done.first + i] = count;
+            }
+            by_length.push(counts);
+            column += 1;
+        }
+
+        by_length
+    }
+}
+
+/// Count the fixed polyominoes whose bounding box has `width` rows,
+/// indexed by number of squares - 1, up to `max` squares
+pub fn count_strip(max: usize, width: usize) -> Vec<u128> {
+    let strip = Strip {
+        width,
+        max,
+        min_columns: 1,
+    };
+    let mut counts = vec![0; max];
+    for by_size in strip.sweep().iter() {
+        for (total, count) in counts.iter_mut().zip(by_size[1..].iter()) {
+            *total += count;
+        }
+    }
+    counts
+}
+
+/// Count fixed polyominoes with 1 to `max` squares, summing over the strip widths.
+/// The strips are swept in parallel
+pub fn count_fixed(max: usize) -> Vec<u128> {
+    let by_width: Vec<_> = thread::scope(|scope| {
+        let sweeps: Vec<_> = (1..=max.div_ceil(2))
+            .map(|width| {
+                scope.spawn(move || {
+                    let strip = Strip {
+                        width,
+                        max,
+                        min_columns: width,
+                    };
+                    (width, strip.sweep())
+                })
+            })
+            .collect();
+        sweeps.into_iter().map(|s| s.join().unwrap()).collect()
+    });
+
+    let mut counts = vec![0; max];
+    for (width, by_length) in by_width.into_iter() {
+        for (columns, by_size) in by_length.iter().enumerate() {
+            // Transpose the polyominoes with more columns than rows
+            let factor = match (columns + 1).cmp(&width) {
+                Ordering::Less => 0,
+                Ordering::Equal => 1,
+                Ordering::Greater => 2,
+            };
+            for (total, count) in counts.iter_mut().zip(by_size[1..].iter()) {
+                *total += factor * count;
+            }
+        }
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::redelmeier;
+
+    use super::*;
+
+    #[test]
+    fn single_row() {
+        assert_eq!(count_strip(6, 1), [1; 6]);
+    }
+
+    #[test]
+    fn two_rows() {
+        // The tetrominoes are O, four L or J, two T, one S and one Z
+        assert_eq!(count_strip(4, 2), [0, 1, 4, 9]);
+    }
+
+    #[test]
+    fn same_counts_as_redelmeier() {
+        assert_eq!(count_fixed(13), redelmeier::count_fixed(13));
+    }
+
+    #[test]
+    fn strips_add_up() {
+        let max = 9;
+        let mut counts = vec![0; max];
+        for width in 1..=max {
+            for (total, count) in counts.iter_mut().zip(count_strip(max, width)) {
+                *total += count;
+            }
+        }
+        assert_eq!(counts, count_fixed(max));
+    }
+}